@@ -0,0 +1,500 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::{AuthError, Scope};
+use crate::config::{JwtConfig, JwtRuleConfig};
+
+/// Lower bound between JWKS fetch attempts, failed ones included, so garbage tokens or an
+/// unreachable issuer cannot turn every request into a fetch.
+const MIN_REFETCH: Duration = Duration::from_secs(30);
+
+const DEFAULT_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// Validates OIDC-issued JWTs against a JWKS and maps their claims to scopes.
+#[derive(Debug)]
+pub struct JwtVerifier {
+    source: JwksSource,
+    issuer: String,
+    audience: Vec<String>,
+    algorithms: Vec<Algorithm>,
+    leeway: Duration,
+    subject_claim: String,
+    rules: Vec<JwtRule>,
+}
+
+#[derive(Debug)]
+enum JwksSource {
+    File(Arc<JwkSet>),
+    Url {
+        url: String,
+        client: reqwest::Client,
+        refresh_interval: Duration,
+        cached: Mutex<JwksCache>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct JwksCache {
+    /// Last good key set and when it was fetched.
+    keys: Option<(Instant, Arc<JwkSet>)>,
+    last_attempt: Option<Instant>,
+    /// Why the last attempt failed, answered until the next one is allowed.
+    last_error: Option<String>,
+}
+
+#[derive(Debug)]
+struct JwtRule {
+    claim: Option<String>,
+    value: Option<String>,
+    scope: Scope,
+}
+
+impl JwtRule {
+    fn from_config(config: &JwtRuleConfig) -> Result<Self, AuthError> {
+        if config.claim.is_some() != config.value.is_some() {
+            return Err(AuthError::InvalidJwtConfig(
+                "rules must set both claim and value, or neither".to_string(),
+            ));
+        }
+        Ok(Self {
+            claim: config.claim.clone(),
+            value: config.value.clone(),
+            scope: Scope::new(&config.buckets, &config.prefixes, &config.methods).ok_or_else(
+                || AuthError::InvalidJwtConfig("rule prefixes must not be empty".to_string()),
+            )?,
+        })
+    }
+
+    fn matches(&self, claims: &Map<String, Value>) -> bool {
+        let (Some(claim), Some(expected)) = (&self.claim, &self.value) else {
+            return true;
+        };
+        match claims.get(claim) {
+            Some(Value::String(value)) => {
+                value == expected || value.split(' ').any(|word| word == expected)
+            }
+            Some(Value::Array(values)) => values
+                .iter()
+                .any(|value| value.as_str() == Some(expected.as_str())),
+            _ => false,
+        }
+    }
+}
+
+impl JwtVerifier {
+    pub fn from_config(config: &JwtConfig) -> Result<Self, AuthError> {
+        let invalid = |message: &str| AuthError::InvalidJwtConfig(message.to_string());
+        let source = match (&config.jwks_url, &config.jwks_file) {
+            (Some(url), None) => JwksSource::Url {
+                url: url.clone(),
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()
+                    .map_err(|err| AuthError::InvalidJwtConfig(err.to_string()))?,
+                refresh_interval: config.refresh_interval,
+                cached: Mutex::new(JwksCache::default()),
+            },
+            (None, Some(path)) => {
+                let body = std::fs::read(path)
+                    .map_err(|err| AuthError::InvalidJwtConfig(format!("{path}: {err}")))?;
+                let keys = serde_json::from_slice(&body)
+                    .map_err(|err| AuthError::InvalidJwtConfig(format!("{path}: {err}")))?;
+                JwksSource::File(Arc::new(keys))
+            }
+            _ => return Err(invalid("set exactly one of jwks_url or jwks_file")),
+        };
+        if config.audience.is_empty() {
+            return Err(invalid("audience must not be empty"));
+        }
+        let algorithms = config
+            .algorithms
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ALGORITHMS.to_vec());
+        // Symmetric keys in a JWKS would let anyone holding them mint tokens.
+        if algorithms
+            .iter()
+            .any(|alg| matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512))
+        {
+            return Err(invalid("HMAC algorithms are not supported"));
+        }
+        let rules = config
+            .rules
+            .iter()
+            .map(JwtRule::from_config)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            source,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            algorithms,
+            leeway: config.leeway,
+            subject_claim: config.subject_claim.clone(),
+            rules,
+        })
+    }
+
+    /// Validates the token and its scope, returning the subject.
+    pub async fn verify(
+        &self,
+        token: &str,
+        method: &str,
+        bucket_id: &str,
+        path: &str,
+    ) -> Result<String, AuthError> {
+        let invalid = |err: jsonwebtoken::errors::Error| AuthError::InvalidJwt(err.to_string());
+        let header = jsonwebtoken::decode_header(token).map_err(invalid)?;
+        if !self.algorithms.contains(&header.alg) {
+            return Err(AuthError::InvalidJwt(format!(
+                "algorithm {:?} not allowed",
+                header.alg
+            )));
+        }
+        let jwk = self.find_key(header.kid.as_deref()).await?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(invalid)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&self.audience);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        validation.leeway = self.leeway.as_secs();
+        let claims = jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+
+        let subject = claims
+            .get(&self.subject_claim)
+            .and_then(Value::as_str)
+            .unwrap_or("unknown")
+            .to_string();
+        let allowed = self
+            .rules
+            .iter()
+            .any(|rule| rule.matches(&claims) && rule.scope.allows(method, bucket_id, path));
+        if allowed {
+            Ok(subject)
+        } else {
+            Err(AuthError::TokenOutOfScope(subject))
+        }
+    }
+
+    /// Picks the key by `kid`, or the only key when the token has none.
+    async fn find_key(&self, kid: Option<&str>) -> Result<Jwk, AuthError> {
+        let select = |keys: &JwkSet| match kid {
+            Some(kid) => keys.find(kid).cloned(),
+            None if keys.keys.len() == 1 => keys.keys.first().cloned(),
+            None => None,
+        };
+        let unknown = || AuthError::InvalidJwt("no matching key in JWKS".to_string());
+
+        let (url, client, refresh_interval, cached) = match &self.source {
+            JwksSource::File(keys) => return select(keys).ok_or_else(unknown),
+            JwksSource::Url {
+                url,
+                client,
+                refresh_interval,
+                cached,
+            } => (url, client, refresh_interval, cached),
+        };
+
+        // Held across the fetch so concurrent requests share one refresh.
+        let mut cached = cached.lock().await;
+        if let Some((fetched_at, keys)) = cached.keys.as_ref()
+            && fetched_at.elapsed() < *refresh_interval
+            && let Some(jwk) = select(keys)
+        {
+            return Ok(jwk);
+        }
+        if cached
+            .last_attempt
+            .is_some_and(|attempt| attempt.elapsed() < MIN_REFETCH)
+        {
+            return match (cached.keys.as_ref(), cached.last_error.as_ref()) {
+                (Some((_, keys)), _) => select(keys).ok_or_else(unknown),
+                (None, Some(err)) => Err(AuthError::JwksUnavailable(err.clone())),
+                (None, None) => Err(unknown()),
+            };
+        }
+
+        cached.last_attempt = Some(Instant::now());
+        match fetch_jwks(client, url).await {
+            Ok(keys) => {
+                let keys = Arc::new(keys);
+                cached.keys = Some((Instant::now(), keys.clone()));
+                cached.last_error = None;
+                select(&keys).ok_or_else(unknown)
+            }
+            // Keep serving the last good set while the issuer is unreachable.
+            Err(err) => {
+                cached.last_error = Some(err.to_string());
+                match cached.keys.as_ref() {
+                    Some((_, keys)) => {
+                        warn!(url = %url, error = %err, "JWKS refresh failed, using cached keys");
+                        select(keys).ok_or_else(unknown)
+                    }
+                    None => Err(AuthError::JwksUnavailable(err.to_string())),
+                }
+            }
+        }
+    }
+}
+
+async fn fetch_jwks(client: &reqwest::Client, url: &str) -> reqwest::Result<JwkSet> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::Router;
+    use axum::routing::get;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use ed25519_dalek::SigningKey;
+    use jsonwebtoken::{EncodingKey, Header};
+    use time::OffsetDateTime;
+
+    use super::*;
+
+    const RULES: &str = r#"
+rules:
+  - claim: scope
+    value: "cachegate:ingest"
+    buckets: [media]
+    prefixes: ["raw/"]
+    methods: [PUT]
+  - claim: groups
+    value: readers
+    methods: [GET, HEAD]
+"#;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[9u8; 32])
+    }
+
+    fn jwks() -> String {
+        let x = URL_SAFE_NO_PAD.encode(signing_key().verifying_key().as_bytes());
+        format!(r#"{{"keys":[{{"kty":"OKP","crv":"Ed25519","kid":"k1","x":"{x}"}}]}}"#)
+    }
+
+    fn verifier(source: &str) -> JwtVerifier {
+        let config: JwtConfig = serde_yaml::from_str(&format!(
+            "{source}\nissuer: https://issuer.test\naudience: [cachegate]\n{RULES}"
+        ))
+        .unwrap();
+        JwtVerifier::from_config(&config).unwrap()
+    }
+
+    fn token(kid: &str, claims: Value) -> String {
+        // PKCS#8 v1 wrapping of the raw Ed25519 seed.
+        let mut der = vec![
+            0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22,
+            0x04, 0x20,
+        ];
+        der.extend_from_slice(&signing_key().to_bytes());
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_ed_der(&der)).unwrap()
+    }
+
+    fn claims(extra: Value) -> Value {
+        let mut claims = serde_json::json!({
+            "sub": "ingest-service",
+            "iss": "https://issuer.test",
+            "aud": "cachegate",
+            "exp": OffsetDateTime::now_utc().unix_timestamp() + 300,
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        claims
+    }
+
+    fn file_verifier() -> (JwtVerifier, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jwks.json");
+        std::fs::write(&path, jwks()).unwrap();
+        (verifier(&format!("jwks_file: {}", path.display())), dir)
+    }
+
+    #[tokio::test]
+    async fn maps_claims_to_scopes() {
+        let (verifier, _dir) = file_verifier();
+        let ingest = token(
+            "k1",
+            claims(serde_json::json!({"scope": "openid cachegate:ingest"})),
+        );
+
+        assert_eq!(
+            verifier
+                .verify(&ingest, "PUT", "media", "raw/a.bin")
+                .await
+                .unwrap(),
+            "ingest-service"
+        );
+        assert!(matches!(
+            verifier.verify(&ingest, "GET", "media", "raw/a.bin").await,
+            Err(AuthError::TokenOutOfScope(subject)) if subject == "ingest-service"
+        ));
+
+        let reader = token("k1", claims(serde_json::json!({"groups": ["readers"]})));
+        assert!(verifier.verify(&reader, "GET", "any", "x").await.is_ok());
+        assert!(
+            verifier
+                .verify(&reader, "PUT", "media", "raw/a.bin")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_tokens() {
+        let (verifier, _dir) = file_verifier();
+        let scope = serde_json::json!({"scope": "cachegate:ingest"});
+        let expired = OffsetDateTime::now_utc().unix_timestamp() - 600;
+
+        for token in [
+            token("k2", claims(scope.clone())),
+            token(
+                "k1",
+                claims(serde_json::json!({"aud": "other", "scope": "cachegate:ingest"})),
+            ),
+            token(
+                "k1",
+                claims(
+                    serde_json::json!({"iss": "https://evil.test", "scope": "cachegate:ingest"}),
+                ),
+            ),
+            token(
+                "k1",
+                claims(serde_json::json!({"exp": expired, "scope": "cachegate:ingest"})),
+            ),
+            jsonwebtoken::encode(
+                &Header::new(Algorithm::HS256),
+                &claims(scope.clone()),
+                &EncodingKey::from_secret(b"secret"),
+            )
+            .unwrap(),
+            "not-a-jwt".to_string(),
+        ] {
+            assert!(
+                matches!(
+                    verifier.verify(&token, "PUT", "media", "raw/a.bin").await,
+                    Err(AuthError::InvalidJwt(_))
+                ),
+                "{token}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_jwks_from_url_and_limits_refetches() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let body = jwks();
+        let app = Router::new().route(
+            "/jwks.json",
+            get({
+                let hits = hits.clone();
+                move || async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    body
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let verifier = verifier(&format!("jwks_url: http://{addr}/jwks.json"));
+        let scope = serde_json::json!({"scope": "cachegate:ingest"});
+
+        for _ in 0..2 {
+            assert!(
+                verifier
+                    .verify(&token("k1", claims(scope.clone())), "PUT", "media", "raw/a")
+                    .await
+                    .is_ok()
+            );
+        }
+        // Unknown kids right after a fetch are answered from the cache.
+        assert!(
+            verifier
+                .verify(&token("k9", claims(scope)), "PUT", "media", "raw/a")
+                .await
+                .is_err()
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn backs_off_after_failed_fetches() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/jwks.json",
+            get({
+                let hits = hits.clone();
+                move || async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let verifier = verifier(&format!("jwks_url: http://{addr}/jwks.json"));
+        let scope = serde_json::json!({"scope": "cachegate:ingest"});
+        for _ in 0..3 {
+            let result = verifier
+                .verify(&token("k1", claims(scope.clone())), "PUT", "media", "raw/a")
+                .await;
+            assert!(matches!(result, Err(AuthError::JwksUnavailable(_))));
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn rejects_bad_configs() {
+        for yaml in [
+            "issuer: i\naudience: [a]",
+            "jwks_file: /nonexistent\nissuer: i\naudience: [a]",
+            "jwks_url: http://x\nissuer: i\naudience: []",
+            "jwks_url: http://x\nissuer: i\naudience: [a]\nalgorithms: [HS256]",
+            "jwks_url: http://x\nissuer: i\naudience: [a]\nrules: [{claim: scope}]",
+        ] {
+            let config: JwtConfig = serde_yaml::from_str(yaml).unwrap();
+            assert!(
+                matches!(
+                    JwtVerifier::from_config(&config),
+                    Err(AuthError::InvalidJwtConfig(_))
+                ),
+                "{yaml}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1290 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, Signer as _, SigningKey, VerifyingKey};
+use ipnet::IpNet;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::config::{AuthConfig, BearerTokenConfig};
+
+mod jwt;
+
+use jwt::JwtVerifier;
+
+/// Characters escaped in a URL path segment; `/` is kept as the separator between segments.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("malformed signature")]
+    MalformedSignature,
+    #[error("malformed payload")]
+    MalformedPayload,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("unsupported version")]
+    UnsupportedVersion,
+    #[error("expired signature")]
+    Expired,
+    #[error("method mismatch")]
+    MethodMismatch,
+    #[error("bucket mismatch")]
+    BucketMismatch,
+    #[error("path mismatch")]
+    PathMismatch,
+    #[error("invalid key material")]
+    InvalidKeyMaterial,
+    #[error("public and private keys do not match")]
+    KeyMismatch,
+    #[error("no verifying key configured")]
+    NoKeys,
+    #[error("duplicate key id {0}")]
+    DuplicateKeyId(String),
+    #[error("unknown key id")]
+    UnknownKey,
+    #[error("key expired")]
+    KeyExpired,
+    #[error("signature not yet valid")]
+    NotYetValid,
+    #[error("client address not allowed")]
+    ClientNotAllowed,
+    #[error("signature already used")]
+    UsesExhausted,
+    #[error("bearer token {0} must set exactly one of token or sha256, and non-empty prefixes")]
+    InvalidToken(String),
+    #[error("duplicate bearer token name {0}")]
+    DuplicateTokenName(String),
+    #[error("bearer token {0} is not allowed for this request")]
+    TokenOutOfScope(String),
+    #[error("invalid jwt config: {0}")]
+    InvalidJwtConfig(String),
+    #[error("invalid jwt: {0}")]
+    InvalidJwt(String),
+    #[error("jwks unavailable: {0}")]
+    JwksUnavailable(String),
+    #[error("ttl must be at most {}", humantime::format_duration(*.0))]
+    TtlTooLong(Duration),
+    #[error("no private key configured that outlives the requested ttl")]
+    NoSigningKey,
+    #[error("missing auth")]
+    MissingAuth,
+    #[error("invalid bearer token")]
+    InvalidBearer,
+    #[error("bearer token not configured")]
+    BearerNotConfigured,
+}
+
+#[derive(Debug, Deserialize)]
+struct PresignPayload {
+    #[serde(rename = "v")]
+    version: u8,
+    #[serde(rename = "exp")]
+    expiry: i64,
+    #[serde(rename = "m")]
+    method: String,
+    #[serde(rename = "b")]
+    bucket_id: String,
+    #[serde(rename = "p")]
+    path: String,
+    /// v2 only: `path` is a prefix and any key below it is authorized.
+    #[serde(default)]
+    prefix: bool,
+    /// Selects a key from `auth.keys`; without it the top-level key is used.
+    #[serde(default)]
+    kid: Option<String>,
+    /// Not valid before this unix timestamp.
+    #[serde(default)]
+    nbf: Option<i64>,
+    /// Client networks allowed to use the signature.
+    #[serde(default)]
+    ip: Vec<IpNet>,
+    #[serde(default)]
+    min_len: Option<u64>,
+    #[serde(default)]
+    max_len: Option<u64>,
+    /// Allowed upload `Content-Type`s; `type/*` matches any subtype.
+    #[serde(default)]
+    ct: Vec<String>,
+    /// Uses allowed before the signature is spent. Signers add a random `nonce` field so
+    /// otherwise identical payloads get their own counter.
+    #[serde(default)]
+    max_uses: Option<u32>,
+}
+
+/// What a verified presign signature allows.
+#[derive(Clone, Debug, Default)]
+pub struct PresignGrant {
+    pub limits: UploadLimits,
+    /// Set for limited-use signatures; the caller counts the use.
+    pub uses: Option<UseLimit>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UseLimit {
+    /// Hex SHA-256 of the signature.
+    pub key: String,
+    pub max_uses: u32,
+    pub expires_at: i64,
+}
+
+/// Body limits a presigned PUT places on the upload, enforced by the handler while streaming.
+#[derive(Clone, Debug, Default)]
+pub struct UploadLimits {
+    pub min_len: Option<u64>,
+    pub max_len: Option<u64>,
+    pub content_types: Vec<String>,
+}
+
+impl UploadLimits {
+    pub fn allows_content_type(&self, content_type: Option<&str>) -> bool {
+        if self.content_types.is_empty() {
+            return true;
+        }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_suffix("/*") {
+                Some(kind) => essence
+                    .split_once('/')
+                    .is_some_and(|(essence_kind, _)| essence_kind == kind),
+                None => essence == allowed,
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
+struct KeyEntry {
+    verifying_key: VerifyingKey,
+    not_after: Option<SystemTime>,
+}
+
+/// Buckets, path prefixes and methods a credential may use. Empty lists allow everything.
+#[derive(Clone, Debug)]
+struct Scope {
+    buckets: Vec<String>,
+    prefixes: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl Scope {
+    /// Returns `None` for an empty prefix, which would never match.
+    fn new(buckets: &[String], prefixes: &[String], methods: &[String]) -> Option<Self> {
+        if prefixes.iter().any(|prefix| prefix.is_empty()) {
+            return None;
+        }
+        Some(Self {
+            buckets: buckets.to_vec(),
+            prefixes: prefixes.to_vec(),
+            methods: methods.to_vec(),
+        })
+    }
+
+    fn is_unscoped(&self) -> bool {
+        self.buckets.is_empty() && self.prefixes.is_empty() && self.methods.is_empty()
+    }
+
+    fn allows(&self, method: &str, bucket_id: &str, path: &str) -> bool {
+        (self.buckets.is_empty() || self.buckets.iter().any(|bucket| bucket == bucket_id))
+            && (self.prefixes.is_empty()
+                || self
+                    .prefixes
+                    .iter()
+                    .any(|prefix| prefix_covers(prefix, path)))
+            && (self.methods.is_empty()
+                || self
+                    .methods
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(method)))
+    }
+}
+
+#[derive(Clone)]
+struct BearerToken {
+    name: String,
+    sha256: [u8; 32],
+    scope: Scope,
+}
+
+impl BearerToken {
+    fn from_config(config: &BearerTokenConfig) -> Result<Self, AuthError> {
+        let invalid = || AuthError::InvalidToken(config.name.clone());
+        let sha256 = match (&config.token, &config.sha256) {
+            (Some(token), None) => Sha256::digest(token.as_bytes()).into(),
+            (None, Some(sha256)) => {
+                let mut bytes = [0u8; 32];
+                hex::decode_to_slice(sha256, &mut bytes).map_err(|_| invalid())?;
+                bytes
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            name: config.name.clone(),
+            sha256,
+            scope: Scope::new(&config.buckets, &config.prefixes, &config.methods)
+                .ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// What to presign. The payload is v1, or v2 for prefix grants.
+#[derive(Clone, Debug)]
+pub struct SignRequest {
+    pub method: String,
+    pub bucket_id: String,
+    pub path: String,
+    pub ttl: Duration,
+    pub prefix: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SignedUrl {
+    pub path: String,
+    /// `/{bucket_id}/{path}?sig=...`, prefixed with the base URL when one is given.
+    pub url: String,
+    pub sig: String,
+    pub expires_at: i64,
+}
+
+#[derive(Serialize)]
+struct SignedPayload<'a> {
+    v: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+    exp: i64,
+    m: &'a str,
+    b: &'a str,
+    p: &'a str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    prefix: bool,
+}
+
+#[derive(Clone)]
+struct Signer {
+    kid: Option<String>,
+    key: SigningKey,
+    not_after: Option<SystemTime>,
+}
+
+#[derive(Clone)]
+pub struct AuthState {
+    default_key: Option<KeyEntry>,
+    keys: HashMap<String, KeyEntry>,
+    bearer_tokens: Vec<BearerToken>,
+    jwt: Option<Arc<JwtVerifier>>,
+    /// Candidates in order of preference: `keys` entries, then the top-level key.
+    signers: Vec<Signer>,
+    trusted_proxies: Vec<IpNet>,
+    max_sign_ttl: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AuthMethod {
+    Bearer,
+    Jwt,
+    Presign,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bearer => "bearer",
+            Self::Jwt => "jwt",
+            Self::Presign => "presign",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthContext {
+    pub method: AuthMethod,
+    /// Name of the bearer token, or the JWT subject, for logs and metrics.
+    pub token: Option<String>,
+    pub limits: UploadLimits,
+}
+
+impl AuthState {
+    pub fn from_config(config: &AuthConfig) -> Result<Self, AuthError> {
+        let mut default_signer = None;
+        let default_key = match (&config.public_key, &config.private_key) {
+            (Some(public_key), Some(private_key)) => {
+                let (verifying_key, signing_key) = parse_key_pair(public_key, Some(private_key))?;
+                default_signer = signing_key.map(|key| Signer {
+                    kid: None,
+                    key,
+                    not_after: None,
+                });
+                Some(KeyEntry {
+                    verifying_key,
+                    not_after: None,
+                })
+            }
+            (None, None) => None,
+            _ => return Err(AuthError::InvalidKeyMaterial),
+        };
+
+        let mut keys = HashMap::new();
+        let mut signers = Vec::new();
+        for key in &config.keys {
+            let (verifying_key, signing_key) =
+                parse_key_pair(&key.public_key, key.private_key.as_deref())?;
+            if let Some(signing_key) = signing_key {
+                signers.push(Signer {
+                    kid: Some(key.kid.clone()),
+                    key: signing_key,
+                    not_after: key.not_after,
+                });
+            }
+            let entry = KeyEntry {
+                verifying_key,
+                not_after: key.not_after,
+            };
+            if keys.insert(key.kid.clone(), entry).is_some() {
+                return Err(AuthError::DuplicateKeyId(key.kid.clone()));
+            }
+        }
+        signers.extend(default_signer);
+        if default_key.is_none() && keys.is_empty() {
+            return Err(AuthError::NoKeys);
+        }
+
+        let legacy = config.bearer_token.as_ref().map(|token| BearerTokenConfig {
+            name: "default".to_string(),
+            token: Some(token.clone()),
+            sha256: None,
+            buckets: Vec::new(),
+            prefixes: Vec::new(),
+            methods: Vec::new(),
+        });
+        let mut bearer_tokens: Vec<BearerToken> = Vec::new();
+        for token in legacy.iter().chain(&config.bearer_tokens) {
+            if bearer_tokens
+                .iter()
+                .any(|existing| existing.name == token.name)
+            {
+                return Err(AuthError::DuplicateTokenName(token.name.clone()));
+            }
+            bearer_tokens.push(BearerToken::from_config(token)?);
+        }
+
+        Ok(Self {
+            default_key,
+            keys,
+            bearer_tokens,
+            signers,
+            jwt: config
+                .jwt
+                .as_ref()
+                .map(JwtVerifier::from_config)
+                .transpose()?
+                .map(Arc::new),
+            trusted_proxies: config.trusted_proxies.clone(),
+            max_sign_ttl: config.max_sign_ttl,
+        })
+    }
+
+    /// Resolves the client address: the peer itself, or the nearest untrusted hop of
+    /// `X-Forwarded-For` while the peer is a trusted proxy.
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let mut client = peer?.to_canonical();
+        let mut hops = forwarded_for
+            .into_iter()
+            .flat_map(|value| value.rsplit(','));
+        while self
+            .trusted_proxies
+            .iter()
+            .any(|proxy| proxy.contains(&client))
+        {
+            let Some(hop) = hops.next() else {
+                break;
+            };
+            // A garbled header from a trusted proxy leaves the client unknown.
+            client = hop.trim().parse::<IpAddr>().ok()?.to_canonical();
+        }
+        Some(client)
+    }
+
+    fn verifying_key(&self, kid: Option<&str>) -> Result<&VerifyingKey, AuthError> {
+        let entry = match kid {
+            Some(kid) => self.keys.get(kid),
+            None => self.default_key.as_ref(),
+        }
+        .ok_or(AuthError::UnknownKey)?;
+        if entry
+            .not_after
+            .is_some_and(|not_after| SystemTime::now() > not_after)
+        {
+            return Err(AuthError::KeyExpired);
+        }
+        Ok(&entry.verifying_key)
+    }
+
+    pub fn verify(
+        &self,
+        method: &str,
+        bucket_id: &str,
+        path: &str,
+        client_ip: Option<IpAddr>,
+        sig: &str,
+    ) -> Result<PresignGrant, AuthError> {
+        let (payload_b64, signature_b64) =
+            sig.split_once('.').ok_or(AuthError::MalformedSignature)?;
+
+        let payload_bytes = URL_SAFE_NO_PAD
+            .decode(payload_b64)
+            .map_err(|_| AuthError::MalformedPayload)?;
+        let payload: PresignPayload =
+            serde_json::from_slice(&payload_bytes).map_err(|_| AuthError::MalformedPayload)?;
+
+        match payload.version {
+            1 if payload.prefix => return Err(AuthError::MalformedPayload),
+            1 | 2 => {}
+            _ => return Err(AuthError::UnsupportedVersion),
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if payload.expiry < now {
+            return Err(AuthError::Expired);
+        }
+        if payload.nbf.is_some_and(|nbf| now < nbf) {
+            return Err(AuthError::NotYetValid);
+        }
+        if payload
+            .min_len
+            .zip(payload.max_len)
+            .is_some_and(|(min, max)| min > max)
+        {
+            return Err(AuthError::MalformedPayload);
+        }
+        if payload.max_uses == Some(0) {
+            return Err(AuthError::MalformedPayload);
+        }
+        if payload.method.to_uppercase() != method.to_uppercase() {
+            return Err(AuthError::MethodMismatch);
+        }
+        if payload.bucket_id != bucket_id {
+            return Err(AuthError::BucketMismatch);
+        }
+        let path_allowed = if payload.prefix {
+            prefix_covers(&payload.path, path)
+        } else {
+            payload.path == path
+        };
+        if !path_allowed {
+            return Err(AuthError::PathMismatch);
+        }
+        if !payload.ip.is_empty()
+            && !client_ip.is_some_and(|ip| payload.ip.iter().any(|net| net.contains(&ip)))
+        {
+            return Err(AuthError::ClientNotAllowed);
+        }
+
+        let signature_bytes = URL_SAFE_NO_PAD
+            .decode(signature_b64)
+            .map_err(|_| AuthError::MalformedSignature)?;
+        let signature = Signature::from_bytes(
+            signature_bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| AuthError::MalformedSignature)?,
+        );
+
+        self.verifying_key(payload.kid.as_deref())?
+            .verify_strict(&payload_bytes, &signature)
+            .map_err(|_| AuthError::InvalidSignature)?;
+
+        Ok(PresignGrant {
+            limits: UploadLimits {
+                min_len: payload.min_len,
+                max_len: payload.max_len,
+                content_types: payload.ct,
+            },
+            uses: payload.max_uses.map(|max_uses| UseLimit {
+                key: format!("{:x}", Sha256::digest(signature_bytes)),
+                max_uses,
+                expires_at: payload.expiry,
+            }),
+        })
+    }
+
+    /// Checks the token and its scope, returning the token name.
+    pub fn verify_bearer(
+        &self,
+        token: &str,
+        method: &str,
+        bucket_id: &str,
+        path: &str,
+    ) -> Result<String, AuthError> {
+        let token = self.find_bearer(token)?;
+        if token.scope.allows(method, bucket_id, path) {
+            Ok(token.name.clone())
+        } else {
+            Err(AuthError::TokenOutOfScope(token.name.clone()))
+        }
+    }
+
+    /// Signs a presigned URL with the first private key, in config order, still valid when the
+    /// URL expires. The newest key goes first in `keys`.
+    pub fn sign(
+        &self,
+        request: &SignRequest,
+        base_url: Option<&str>,
+    ) -> Result<SignedUrl, AuthError> {
+        let too_long = || AuthError::TtlTooLong(self.max_sign_ttl);
+        if request.ttl > self.max_sign_ttl {
+            return Err(too_long());
+        }
+        let expires = SystemTime::now()
+            .checked_add(request.ttl)
+            .ok_or_else(too_long)?;
+        let expires_at = i64::try_from(request.ttl.as_secs())
+            .ok()
+            .and_then(|ttl| OffsetDateTime::now_utc().unix_timestamp().checked_add(ttl))
+            .ok_or_else(too_long)?;
+        let signer = self
+            .signers
+            .iter()
+            .find(|signer| {
+                signer
+                    .not_after
+                    .is_none_or(|not_after| expires <= not_after)
+            })
+            .ok_or(AuthError::NoSigningKey)?;
+        let method = request.method.to_uppercase();
+        let payload = serde_json::to_vec(&SignedPayload {
+            v: if request.prefix { 2 } else { 1 },
+            kid: signer.kid.as_deref(),
+            exp: expires_at,
+            m: &method,
+            b: &request.bucket_id,
+            p: &request.path,
+            prefix: request.prefix,
+        })
+        .map_err(|_| AuthError::MalformedPayload)?;
+        let signature = signer.key.sign(&payload);
+        let sig = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+
+        let path = request
+            .path
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        let url = format!(
+            "{}/{}/{path}?sig={sig}",
+            base_url.unwrap_or_default().trim_end_matches('/'),
+            utf8_percent_encode(&request.bucket_id, PATH_SEGMENT),
+        );
+        Ok(SignedUrl {
+            path: request.path.clone(),
+            url,
+            sig,
+            expires_at,
+        })
+    }
+
+    pub fn accepts_jwt(&self) -> bool {
+        self.jwt.is_some()
+    }
+
+    /// Validates a JWT and its scope, returning the subject.
+    pub async fn verify_jwt(
+        &self,
+        token: &str,
+        method: &str,
+        bucket_id: &str,
+        path: &str,
+    ) -> Result<String, AuthError> {
+        let jwt = self.jwt.as_ref().ok_or(AuthError::InvalidBearer)?;
+        jwt.verify(token, method, bucket_id, path).await
+    }
+
+    /// Admin endpoints need a token without any scope.
+    pub fn verify_admin_bearer(&self, token: &str) -> Result<String, AuthError> {
+        let token = self.find_bearer(token)?;
+        if token.scope.is_unscoped() {
+            Ok(token.name.clone())
+        } else {
+            Err(AuthError::TokenOutOfScope(token.name.clone()))
+        }
+    }
+
+    /// Compares hashes in constant time and visits every token, so timing reveals neither
+    /// the token nor which entry matched.
+    fn find_bearer(&self, token: &str) -> Result<&BearerToken, AuthError> {
+        if self.bearer_tokens.is_empty() {
+            return Err(AuthError::BearerNotConfigured);
+        }
+        let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        let mut found = None;
+        for candidate in &self.bearer_tokens {
+            if bool::from(candidate.sha256.ct_eq(&digest)) {
+                found = Some(candidate);
+            }
+        }
+        found.ok_or(AuthError::InvalidBearer)
+    }
+}
+
+/// Matches whole segments only, and never a path with empty, `.` or `..` segments, so a
+/// request cannot climb out of the signed prefix.
+fn prefix_covers(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    if !is_normalized(prefix) || !is_normalized(path) {
+        return false;
+    }
+    path.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .is_some_and(|rest| !rest.is_empty())
+}
+
+fn is_normalized(path: &str) -> bool {
+    path.split('/')
+        .all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+}
+
+/// Parses a public key, checking it against the private key when one is given.
+fn parse_key_pair(
+    public_key: &str,
+    private_key: Option<&str>,
+) -> Result<(VerifyingKey, Option<SigningKey>), AuthError> {
+    let public_key = VerifyingKey::from_bytes(
+        &decode_key(public_key)?
+            .try_into()
+            .map_err(|_| AuthError::InvalidKeyMaterial)?,
+    )
+    .map_err(|_| AuthError::InvalidKeyMaterial)?;
+
+    let signing_key = match private_key {
+        Some(private_key) => {
+            let signing_key = SigningKey::from_bytes(
+                &decode_key(private_key)?
+                    .try_into()
+                    .map_err(|_| AuthError::InvalidKeyMaterial)?,
+            );
+            if signing_key.verifying_key() != public_key {
+                return Err(AuthError::KeyMismatch);
+            }
+            Some(signing_key)
+        }
+        None => None,
+    };
+    Ok((public_key, signing_key))
+}
+
+fn decode_key(input: &str) -> Result<Vec<u8>, AuthError> {
+    URL_SAFE_NO_PAD
+        .decode(input)
+        .map_err(|_| AuthError::InvalidKeyMaterial)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ed25519_dalek::Signer;
+
+    use crate::config::AuthKeyConfig;
+
+    use super::*;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    fn auth_config() -> AuthConfig {
+        let key = signing_key();
+        AuthConfig {
+            public_key: Some(URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes())),
+            private_key: Some(URL_SAFE_NO_PAD.encode(key.to_bytes())),
+            keys: Vec::new(),
+            bearer_token: None,
+            bearer_tokens: Vec::new(),
+            jwt: None,
+            trusted_proxies: Vec::new(),
+            replay: Default::default(),
+            max_sign_ttl: Duration::from_secs(3600),
+        }
+    }
+
+    fn auth() -> AuthState {
+        AuthState::from_config(&auth_config()).unwrap()
+    }
+
+    fn sign(payload: serde_json::Value) -> String {
+        sign_with(&signing_key(), payload)
+    }
+
+    fn sign_with(key: &SigningKey, payload: serde_json::Value) -> String {
+        let bytes = serde_json::to_vec(&payload).unwrap();
+        let signature = key.sign(&bytes);
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&bytes),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    fn prefix_sig(version: u8, prefix: &str) -> String {
+        sign(serde_json::json!({
+            "v": version,
+            "exp": OffsetDateTime::now_utc().unix_timestamp() + 300,
+            "m": "GET",
+            "b": "media",
+            "p": prefix,
+            "prefix": true,
+        }))
+    }
+
+    #[test]
+    fn prefix_grant_covers_keys_below_it() {
+        let auth = auth();
+        let sig = prefix_sig(2, "albums/123/");
+
+        assert!(
+            auth.verify("GET", "media", "albums/123/thumb.jpg", None, &sig)
+                .is_ok()
+        );
+        assert!(
+            auth.verify("GET", "media", "albums/123/a/b.jpg", None, &sig)
+                .is_ok()
+        );
+        assert!(
+            auth.verify("GET", "other", "albums/123/thumb.jpg", None, &sig)
+                .is_err()
+        );
+        assert!(
+            auth.verify("PUT", "media", "albums/123/thumb.jpg", None, &sig)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn prefix_grant_cannot_be_escaped() {
+        let auth = auth();
+        let sig = prefix_sig(2, "albums/123");
+
+        for path in [
+            "albums/1234/thumb.jpg",
+            "albums/123",
+            "albums/123/",
+            "albums/123/../456/thumb.jpg",
+            "albums/123/./thumb.jpg",
+            "albums/123//thumb.jpg",
+            "albums/456/thumb.jpg",
+        ] {
+            assert!(
+                matches!(
+                    auth.verify("GET", "media", path, None, &sig),
+                    Err(AuthError::PathMismatch)
+                ),
+                "{path} should not be covered"
+            );
+        }
+    }
+
+    #[test]
+    fn prefix_flag_requires_v2() {
+        assert!(matches!(
+            auth().verify(
+                "GET",
+                "media",
+                "albums/123/a.jpg",
+                None,
+                &prefix_sig(1, "albums/123/")
+            ),
+            Err(AuthError::MalformedPayload)
+        ));
+    }
+
+    #[test]
+    fn v2_without_prefix_is_exact() {
+        let sig = sign(serde_json::json!({
+            "v": 2,
+            "exp": OffsetDateTime::now_utc().unix_timestamp() + 300,
+            "m": "GET",
+            "b": "media",
+            "p": "albums/123/a.jpg",
+        }));
+
+        assert!(
+            auth()
+                .verify("GET", "media", "albums/123/a.jpg", None, &sig)
+                .is_ok()
+        );
+        assert!(
+            auth()
+                .verify("GET", "media", "albums/123/b.jpg", None, &sig)
+                .is_err()
+        );
+    }
+
+    fn key_config(kid: &str, key: &SigningKey, not_after: Option<SystemTime>) -> AuthKeyConfig {
+        AuthKeyConfig {
+            kid: kid.to_string(),
+            public_key: URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes()),
+            private_key: None,
+            not_after,
+        }
+    }
+
+    fn kid_sig(key: &SigningKey, kid: &str) -> String {
+        sign_with(
+            key,
+            serde_json::json!({
+                "v": 1,
+                "exp": OffsetDateTime::now_utc().unix_timestamp() + 300,
+                "m": "GET",
+                "b": "media",
+                "p": "a.jpg",
+                "kid": kid,
+            }),
+        )
+    }
+
+    #[test]
+    fn kid_selects_verifying_key() {
+        let old = SigningKey::from_bytes(&[1u8; 32]);
+        let new = SigningKey::from_bytes(&[2u8; 32]);
+        let expired = SystemTime::now() - Duration::from_secs(60);
+        let auth = AuthState::from_config(&AuthConfig {
+            public_key: None,
+            private_key: None,
+            keys: vec![
+                key_config("2026-10", &new, None),
+                key_config("2026-09", &old, Some(expired)),
+            ],
+            ..auth_config()
+        })
+        .unwrap();
+
+        assert!(
+            auth.verify("GET", "media", "a.jpg", None, &kid_sig(&new, "2026-10"))
+                .is_ok()
+        );
+        assert!(matches!(
+            auth.verify("GET", "media", "a.jpg", None, &kid_sig(&new, "2026-09")),
+            Err(AuthError::KeyExpired)
+        ));
+        assert!(matches!(
+            auth.verify("GET", "media", "a.jpg", None, &kid_sig(&old, "2026-10")),
+            Err(AuthError::InvalidSignature)
+        ));
+        assert!(matches!(
+            auth.verify("GET", "media", "a.jpg", None, &kid_sig(&new, "unknown")),
+            Err(AuthError::UnknownKey)
+        ));
+        // Without a top-level key pair, kid-less payloads have nothing to verify against.
+        assert!(matches!(
+            auth.verify(
+                "GET",
+                "media",
+                "albums/123/a.jpg",
+                None,
+                &prefix_sig(2, "albums/123/")
+            ),
+            Err(AuthError::UnknownKey)
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_key_sets() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let other = SigningKey::from_bytes(&[2u8; 32]);
+        let config = |keys| AuthConfig {
+            public_key: None,
+            private_key: None,
+            keys,
+            ..auth_config()
+        };
+
+        assert!(matches!(
+            AuthState::from_config(&config(Vec::new())),
+            Err(AuthError::NoKeys)
+        ));
+        assert!(matches!(
+            AuthState::from_config(&config(vec![
+                key_config("a", &key, None),
+                key_config("a", &other, None),
+            ])),
+            Err(AuthError::DuplicateKeyId(kid)) if kid == "a"
+        ));
+        let mut mismatched = key_config("a", &key, None);
+        mismatched.private_key = Some(URL_SAFE_NO_PAD.encode(other.to_bytes()));
+        assert!(matches!(
+            AuthState::from_config(&config(vec![mismatched])),
+            Err(AuthError::KeyMismatch)
+        ));
+    }
+
+    fn constrained_sig(constraints: serde_json::Value) -> String {
+        let mut payload = serde_json::json!({
+            "v": 1,
+            "exp": OffsetDateTime::now_utc().unix_timestamp() + 300,
+            "m": "PUT",
+            "b": "media",
+            "p": "upload.png",
+        });
+        payload
+            .as_object_mut()
+            .unwrap()
+            .extend(constraints.as_object().unwrap().clone());
+        sign(payload)
+    }
+
+    #[test]
+    fn enforces_not_before_and_client_networks() {
+        let auth = auth();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let verify = |sig: &str, ip: Option<&str>| {
+            auth.verify(
+                "PUT",
+                "media",
+                "upload.png",
+                ip.map(|ip| ip.parse().unwrap()),
+                sig,
+            )
+        };
+
+        assert!(matches!(
+            verify(&constrained_sig(serde_json::json!({"nbf": now + 60})), None),
+            Err(AuthError::NotYetValid)
+        ));
+        assert!(verify(&constrained_sig(serde_json::json!({"nbf": now - 60})), None).is_ok());
+
+        let sig = constrained_sig(serde_json::json!({"ip": ["10.0.0.0/8", "2001:db8::/32"]}));
+        assert!(verify(&sig, Some("10.1.2.3")).is_ok());
+        assert!(verify(&sig, Some("2001:db8::1")).is_ok());
+        assert!(matches!(
+            verify(&sig, Some("192.168.1.1")),
+            Err(AuthError::ClientNotAllowed)
+        ));
+        assert!(matches!(
+            verify(&sig, None),
+            Err(AuthError::ClientNotAllowed)
+        ));
+        assert!(matches!(
+            verify(&constrained_sig(serde_json::json!({"ip": ["nope"]})), None),
+            Err(AuthError::MalformedPayload)
+        ));
+    }
+
+    #[test]
+    fn returns_upload_limits() {
+        let sig = constrained_sig(serde_json::json!({
+            "min_len": 1,
+            "max_len": 1024,
+            "ct": ["image/*", "application/pdf"],
+        }));
+        let grant = auth()
+            .verify("PUT", "media", "upload.png", None, &sig)
+            .unwrap();
+        assert!(grant.uses.is_none());
+        let limits = grant.limits;
+
+        assert_eq!(limits.min_len, Some(1));
+        assert_eq!(limits.max_len, Some(1024));
+        assert!(limits.allows_content_type(Some("image/png")));
+        assert!(limits.allows_content_type(Some("Application/PDF; charset=binary")));
+        assert!(!limits.allows_content_type(Some("text/html")));
+        assert!(!limits.allows_content_type(Some("imagex/png")));
+        assert!(!limits.allows_content_type(None));
+        assert!(UploadLimits::default().allows_content_type(None));
+
+        let inverted = constrained_sig(serde_json::json!({"min_len": 10, "max_len": 1}));
+        assert!(matches!(
+            auth().verify("PUT", "media", "upload.png", None, &inverted),
+            Err(AuthError::MalformedPayload)
+        ));
+    }
+
+    #[test]
+    fn limited_use_signatures_carry_a_use_key() {
+        let auth = auth();
+        let first = constrained_sig(serde_json::json!({"max_uses": 1, "nonce": "a"}));
+        let second = constrained_sig(serde_json::json!({"max_uses": 1, "nonce": "b"}));
+        let uses = |sig: &str| {
+            auth.verify("PUT", "media", "upload.png", None, sig)
+                .unwrap()
+                .uses
+                .unwrap()
+        };
+
+        let (first_uses, second_uses) = (uses(&first), uses(&second));
+        assert_eq!(first_uses.max_uses, 1);
+        assert_eq!(first_uses.key.len(), 64);
+        assert_eq!(first_uses.key, uses(&first).key);
+        assert_ne!(first_uses.key, second_uses.key);
+        assert!(matches!(
+            auth.verify(
+                "PUT",
+                "media",
+                "upload.png",
+                None,
+                &constrained_sig(serde_json::json!({"max_uses": 0}))
+            ),
+            Err(AuthError::MalformedPayload)
+        ));
+    }
+
+    #[test]
+    fn resolves_client_through_trusted_proxies() {
+        let auth = AuthState {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..auth()
+        };
+        let ip = |addr: &str| Some(addr.parse::<IpAddr>().unwrap());
+
+        // Untrusted peers cannot spoof their address.
+        assert_eq!(
+            auth.client_ip(ip("203.0.113.9"), Some("1.2.3.4")),
+            ip("203.0.113.9")
+        );
+        // Trusted hops are skipped from the right.
+        assert_eq!(
+            auth.client_ip(ip("10.0.0.1"), Some("1.2.3.4, 198.51.100.7, 10.0.0.2")),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            auth.client_ip(ip("::ffff:10.0.0.1"), Some("198.51.100.7")),
+            ip("198.51.100.7")
+        );
+        assert_eq!(auth.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+        assert_eq!(auth.client_ip(ip("10.0.0.1"), Some("garbage")), None);
+    }
+
+    fn token_auth(bearer_token: Option<&str>, tokens: &str) -> Result<AuthState, AuthError> {
+        AuthState::from_config(&AuthConfig {
+            bearer_token: bearer_token.map(str::to_string),
+            bearer_tokens: serde_yaml::from_str(tokens).unwrap(),
+            ..auth_config()
+        })
+    }
+
+    #[test]
+    fn scoped_tokens_match_hash_and_scope() {
+        let ingest_hash = format!("{:x}", Sha256::digest(b"ingest-secret"));
+        let auth = token_auth(
+            Some("legacy"),
+            &format!(
+                r#"
+- name: ingest-service
+  sha256: "{ingest_hash}"
+  buckets: [media]
+  prefixes: ["raw/"]
+  methods: [PUT]
+- name: reader
+  token: "reader-secret"
+  methods: [GET, HEAD]
+"#
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            auth.verify_bearer("ingest-secret", "PUT", "media", "raw/a.bin")
+                .unwrap(),
+            "ingest-service"
+        );
+        for (method, bucket, path) in [
+            ("GET", "media", "raw/a.bin"),
+            ("PUT", "other", "raw/a.bin"),
+            ("PUT", "media", "rawx/a.bin"),
+            ("PUT", "media", "raw/../a.bin"),
+        ] {
+            assert!(matches!(
+                auth.verify_bearer("ingest-secret", method, bucket, path),
+                Err(AuthError::TokenOutOfScope(name)) if name == "ingest-service"
+            ));
+        }
+        assert_eq!(
+            auth.verify_bearer("reader-secret", "head", "any", "x")
+                .unwrap(),
+            "reader"
+        );
+        assert_eq!(
+            auth.verify_bearer("legacy", "DELETE", "any", "x").unwrap(),
+            "default"
+        );
+        assert!(matches!(
+            auth.verify_bearer("nope", "GET", "media", "x"),
+            Err(AuthError::InvalidBearer)
+        ));
+
+        assert_eq!(auth.verify_admin_bearer("legacy").unwrap(), "default");
+        assert!(matches!(
+            auth.verify_admin_bearer("reader-secret"),
+            Err(AuthError::TokenOutOfScope(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_token_configs() {
+        assert!(matches!(
+            auth().verify_bearer("x", "GET", "media", "x"),
+            Err(AuthError::BearerNotConfigured)
+        ));
+        assert!(matches!(
+            token_auth(Some("a"), "[{name: default, token: b}]"),
+            Err(AuthError::DuplicateTokenName(name)) if name == "default"
+        ));
+        for tokens in [
+            "[{name: t}]",
+            "[{name: t, token: a, sha256: abcd}]",
+            "[{name: t, sha256: not-hex}]",
+            "[{name: t, token: a, prefixes: ['']}]",
+        ] {
+            assert!(
+                matches!(token_auth(None, tokens), Err(AuthError::InvalidToken(_))),
+                "{tokens}"
+            );
+        }
+    }
+
+    fn sign_request(path: &str, prefix: bool) -> SignRequest {
+        SignRequest {
+            method: "get".to_string(),
+            bucket_id: "media".to_string(),
+            path: path.to_string(),
+            ttl: Duration::from_secs(300),
+            prefix,
+        }
+    }
+
+    #[test]
+    fn signed_urls_verify() {
+        let auth = auth();
+        let signed = auth
+            .sign(
+                &sign_request("albums/a b#1.jpg", false),
+                Some("https://cdn.test/"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            signed.url,
+            format!(
+                "https://cdn.test/media/albums/a%20b%231.jpg?sig={}",
+                signed.sig
+            )
+        );
+        assert!(
+            auth.verify("GET", "media", "albums/a b#1.jpg", None, &signed.sig)
+                .is_ok()
+        );
+
+        let grant = auth.sign(&sign_request("albums/", true), None).unwrap();
+        assert!(grant.url.starts_with("/media/albums/?sig="));
+        assert!(
+            auth.verify("GET", "media", "albums/x.jpg", None, &grant.sig)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rejects_ttl_above_maximum() {
+        let auth = auth();
+        for ttl in [Duration::from_secs(3601), Duration::MAX] {
+            let request = SignRequest {
+                ttl,
+                ..sign_request("a.jpg", false)
+            };
+            assert!(matches!(
+                auth.sign(&request, None),
+                Err(AuthError::TtlTooLong(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn signs_with_first_live_kid_key() {
+        let retired = SigningKey::from_bytes(&[1u8; 32]);
+        let current = SigningKey::from_bytes(&[2u8; 32]);
+        let with_private = |kid: &str, key: &SigningKey, not_after| AuthKeyConfig {
+            private_key: Some(URL_SAFE_NO_PAD.encode(key.to_bytes())),
+            ..key_config(kid, key, not_after)
+        };
+        let auth = AuthState::from_config(&AuthConfig {
+            keys: vec![
+                with_private(
+                    "old",
+                    &retired,
+                    Some(SystemTime::now() - Duration::from_secs(1)),
+                ),
+                with_private("new", &current, None),
+            ],
+            ..auth_config()
+        })
+        .unwrap();
+
+        let signed = auth.sign(&sign_request("a.jpg", false), None).unwrap();
+        let (payload, _) = signed.sig.split_once('.').unwrap();
+        let payload: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        assert_eq!(payload["kid"], "new");
+        assert!(
+            auth.verify("GET", "media", "a.jpg", None, &signed.sig)
+                .is_ok()
+        );
+
+        // A key that expires before the URL would is skipped, whenever the check runs.
+        let short_lived = AuthState::from_config(&AuthConfig {
+            public_key: None,
+            private_key: None,
+            keys: vec![with_private(
+                "new",
+                &current,
+                Some(SystemTime::now() + Duration::from_secs(60)),
+            )],
+            ..auth_config()
+        })
+        .unwrap();
+        assert!(matches!(
+            short_lived.sign(&sign_request("a.jpg", false), None),
+            Err(AuthError::NoSigningKey)
+        ));
+
+        let verify_only = AuthState::from_config(&AuthConfig {
+            public_key: None,
+            private_key: None,
+            keys: vec![key_config("new", &current, None)],
+            ..auth_config()
+        })
+        .unwrap();
+        assert!(matches!(
+            verify_only.sign(&sign_request("a.jpg", false), None),
+            Err(AuthError::NoSigningKey)
+        ));
+    }
+}
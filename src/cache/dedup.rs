@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+use crate::cache::{CacheBackend, CacheEntry, CacheHealth, CacheKey, CacheStats};
+
+// Namespaces are prefixed with a NUL byte so they can never collide with a routable bucket id.
+const REF_NAMESPACE: &str = "\0ref";
+const CONTENT_NAMESPACE: &str = "\0sha256";
+
+/// Content-addressed layer over another backend.
+///
+/// Path keys map to a SHA-256 digest and the digest maps to the bytes, so identical objects
+/// stored under different paths occupy cache space once.
+pub struct DedupCache<C: CacheBackend> {
+    inner: C,
+    /// One per `put`; the inner backend sees two writes for each.
+    inserts: AtomicU64,
+}
+
+impl<C: CacheBackend> DedupCache<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            inserts: AtomicU64::new(0),
+        }
+    }
+}
+
+pub fn content_digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn ref_key(key: &CacheKey) -> CacheKey {
+    CacheKey::new(
+        format!("{REF_NAMESPACE}/{}", key.bucket_id),
+        key.path.clone(),
+    )
+    .with_generation(key.generation)
+}
+
+fn content_key(digest: &str) -> CacheKey {
+    CacheKey::new(CONTENT_NAMESPACE.to_string(), digest.to_string())
+}
+
+#[async_trait]
+impl<C: CacheBackend> CacheBackend for DedupCache<C> {
+    #[tracing::instrument(skip(self))]
+    async fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        // Reference entries carry the hex digest as their payload.
+        let reference = self.inner.get(&ref_key(key)).await?;
+        let digest = std::str::from_utf8(&reference.bytes).ok()?.to_string();
+        // The content entry may have been evicted independently of the reference.
+        let content = self.inner.get(&content_key(&digest)).await?;
+        Some(CacheEntry::new(content.bytes, reference.content_type).with_digest(Some(digest)))
+    }
+
+    #[tracing::instrument(skip(self, bytes, content_type))]
+    async fn put(
+        &self,
+        key: CacheKey,
+        bytes: Bytes,
+        content_type: Option<String>,
+    ) -> Option<String> {
+        let digest = content_digest(&bytes);
+        self.inner.put(content_key(&digest), bytes, None).await;
+        self.inner
+            .put(
+                ref_key(&key),
+                Bytes::from(digest.clone().into_bytes()),
+                content_type,
+            )
+            .await;
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+        Some(digest)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn stats(&self) -> CacheStats {
+        CacheStats {
+            inserts: self.inserts.load(Ordering::Relaxed),
+            ..self.inner.stats().await
+        }
+    }
+
+    async fn health(&self) -> CacheHealth {
+        self.inner.health().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use bytesize::ByteSize;
+
+    use super::*;
+    use crate::cache::foyer::FoyerCache;
+    use crate::config::CachePolicy;
+
+    async fn make_cache() -> DedupCache<FoyerCache> {
+        let policy = CachePolicy {
+            max_memory: ByteSize(1024 * 1024),
+            max_object_size: ByteSize(1024 * 1024),
+            max_disk: ByteSize(0),
+            disk_path: None,
+            dedup: true,
+            backend: Default::default(),
+            remote: None,
+        };
+        let registry = Box::new(mixtrics::registry::noop::NoopMetricsRegistry);
+        DedupCache::new(FoyerCache::new(policy, registry).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn identical_content_shares_digest() {
+        let cache = make_cache().await;
+        let data = Bytes::from_static(b"same bytes");
+
+        let first = cache
+            .put(
+                CacheKey::new("a".to_string(), "one.txt".to_string()),
+                data.clone(),
+                Some("text/plain".to_string()),
+            )
+            .await;
+        let second = cache
+            .put(
+                CacheKey::new("b".to_string(), "two.bin".to_string()),
+                data.clone(),
+                Some("application/octet-stream".to_string()),
+            )
+            .await;
+
+        assert!(first.is_some());
+        assert_eq!(first, second);
+        assert_eq!(first.as_deref(), Some(content_digest(&data).as_str()));
+        assert_eq!(cache.stats().await.inserts, 2);
+    }
+
+    #[tokio::test]
+    async fn get_resolves_reference_with_own_content_type() {
+        let cache = make_cache().await;
+        let data = Bytes::from_static(b"shared");
+        let key_a = CacheKey::new("a".to_string(), "one.txt".to_string());
+        let key_b = CacheKey::new("b".to_string(), "two.bin".to_string());
+
+        cache
+            .put(key_a.clone(), data.clone(), Some("text/plain".to_string()))
+            .await;
+        cache.put(key_b.clone(), data.clone(), None).await;
+
+        let entry_a = cache.get(&key_a).await.unwrap();
+        let entry_b = cache.get(&key_b).await.unwrap();
+        assert_eq!(entry_a.bytes, data);
+        assert_eq!(entry_b.bytes, data);
+        assert_eq!(entry_a.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(entry_b.content_type, None);
+        assert_eq!(entry_a.digest, entry_b.digest);
+    }
+
+    #[tokio::test]
+    async fn get_returns_none_for_missing_key() {
+        let cache = make_cache().await;
+        let key = CacheKey::new("a".to_string(), "missing.txt".to_string());
+        assert!(cache.get(&key).await.is_none());
+    }
+}
@@ -10,14 +10,22 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tracing::{info, warn};
 
-use crate::cache::{CacheBackend, CacheEntry as CacheEntryInner, CacheKey, CacheStats};
+use crate::cache::{
+    CacheBackend, CacheEntry as CacheEntryInner, CacheHealth, CacheKey, CacheStats,
+};
 use crate::config::CachePolicy;
+use crate::ready::ProbeStatus;
+
+/// Written and removed in `disk_path` by the health probe.
+const HEALTH_SENTINEL: &str = ".cachegate-health";
 
 type FoyerHybridCache = HybridCache<CacheKey, CacheEntryInner>;
 
 pub struct FoyerCache {
     cache: FoyerHybridCache,
     inserts: AtomicU64,
+    /// Set when the disk tier is enabled.
+    disk_path: Option<PathBuf>,
 }
 
 impl FoyerCache {
@@ -44,50 +52,54 @@ impl FoyerCache {
             .with_shards(10) // TODO: have this in config
             .with_eviction_config(S3FifoConfig::default());
 
-        let cache = if disk_capacity == 0 {
-            let cache = builder
-                .storage()
-                .build()
-                .await
-                .context("Failed to initialise cache")?;
-            info!(
-                memory_capacity_bytes = max_bytes_memory,
-                "Foyer cache initialized (memory-only)"
-            );
-            cache
-        } else {
-            let disk_path = disk_path
-                .clone()
-                .unwrap_or_else(|| PathBuf::from("/tmp/cachegate_cache"));
-            std::fs::create_dir_all(&disk_path).context("failed to create disk cache directory")?;
-
-            let device = FsDeviceBuilder::new(&disk_path)
-                .with_capacity(disk_capacity as usize)
-                // TODO: Allow throttling config
-                // TODO: Use direct unbuffered i/o on linux!
-                .build()
-                .context("failed to build disk cache device")?;
-
-            let cache = builder
-                .storage()
-                .with_io_engine_config(PsyncIoEngineConfig::new())
-                .with_engine_config(BlockEngineConfig::new(device))
-                .with_recover_mode(foyer::RecoverMode::Quiet)
-                .build()
-                .await
-                .context("Failed to initialise cache")?;
-            info!(
-                memory_capacity_bytes = max_bytes_memory,
-                disk_capacity_bytes = disk_capacity,
-                disk_path = %disk_path.display(),
-                "Foyer hybrid cache initialized"
-            );
-            cache
+        let disk_path = (disk_capacity > 0)
+            .then(|| disk_path.unwrap_or_else(|| PathBuf::from("/tmp/cachegate_cache")));
+        let cache = match &disk_path {
+            None => {
+                let cache = builder
+                    .storage()
+                    .build()
+                    .await
+                    .context("Failed to initialise cache")?;
+                info!(
+                    memory_capacity_bytes = max_bytes_memory,
+                    "Foyer cache initialized (memory-only)"
+                );
+                cache
+            }
+            Some(disk_path) => {
+                std::fs::create_dir_all(disk_path)
+                    .context("failed to create disk cache directory")?;
+
+                let device = FsDeviceBuilder::new(disk_path)
+                    .with_capacity(disk_capacity as usize)
+                    // TODO: Allow throttling config
+                    // TODO: Use direct unbuffered i/o on linux!
+                    .build()
+                    .context("failed to build disk cache device")?;
+
+                let cache = builder
+                    .storage()
+                    .with_io_engine_config(PsyncIoEngineConfig::new())
+                    .with_engine_config(BlockEngineConfig::new(device))
+                    .with_recover_mode(foyer::RecoverMode::Quiet)
+                    .build()
+                    .await
+                    .context("Failed to initialise cache")?;
+                info!(
+                    memory_capacity_bytes = max_bytes_memory,
+                    disk_capacity_bytes = disk_capacity,
+                    disk_path = %disk_path.display(),
+                    "Foyer hybrid cache initialized"
+                );
+                cache
+            }
         };
 
         Ok(Self {
             cache,
             inserts: AtomicU64::new(0),
+            disk_path,
         })
     }
 }
@@ -110,16 +122,50 @@ impl CacheBackend for FoyerCache {
     }
 
     #[tracing::instrument(skip(self, bytes, content_type))]
-    async fn put(&self, key: CacheKey, bytes: Bytes, content_type: Option<String>) {
+    async fn put(
+        &self,
+        key: CacheKey,
+        bytes: Bytes,
+        content_type: Option<String>,
+    ) -> Option<String> {
         let entry = CacheEntryInner::new(bytes, content_type);
         self.cache.insert(key, entry);
         self.inserts.fetch_add(1, Ordering::Relaxed);
+        None
     }
 
     #[tracing::instrument(skip(self))]
     async fn stats(&self) -> CacheStats {
         CacheStats {
             inserts: self.inserts.load(Ordering::Relaxed),
+            bytes: 0,
+        }
+    }
+
+    /// The disk tier recovers quietly, so a sentinel file is written and read back to catch
+    /// a missing, read-only or full disk.
+    async fn health(&self) -> CacheHealth {
+        let Some(disk_path) = &self.disk_path else {
+            return CacheHealth::ok();
+        };
+        let sentinel = disk_path.join(HEALTH_SENTINEL);
+        let written = Bytes::from(format!("{}", std::process::id()));
+        let result = async {
+            tokio::fs::write(&sentinel, &written).await?;
+            let read = tokio::fs::read(&sentinel).await?;
+            tokio::fs::remove_file(&sentinel).await?;
+            if read != written {
+                return Err(std::io::Error::other("sentinel read back differently"));
+            }
+            Ok(())
+        }
+        .await;
+        match result {
+            Ok(()) => CacheHealth::ok(),
+            Err(err) => CacheHealth {
+                status: ProbeStatus::Down,
+                error: Some(format!("{}: {err}", sentinel.display())),
+            },
         }
     }
 }
@@ -147,6 +193,9 @@ mod tests {
             max_object_size: ByteSize(max_memory_bytes),
             max_disk: ByteSize(max_disk_bytes),
             disk_path,
+            dedup: false,
+            backend: Default::default(),
+            remote: None,
         }
     }
 
@@ -176,6 +225,26 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn health_writes_to_the_disk_tier() {
+        let disk_dir = TempDir::new().unwrap();
+        let policy = make_policy(
+            60,
+            1024 * 1024,
+            Some(disk_dir.path().to_string_lossy().to_string()),
+        );
+        let cache = FoyerCache::new(policy, noop_registry()).await.unwrap();
+
+        assert_eq!(cache.health().await.status, ProbeStatus::Ok);
+        assert!(!disk_dir.path().join(HEALTH_SENTINEL).exists());
+
+        // Stands in for a read-only or full disk: the sentinel cannot be written.
+        std::fs::create_dir(disk_dir.path().join(HEALTH_SENTINEL)).unwrap();
+        let health = cache.health().await;
+        assert_eq!(health.status, ProbeStatus::Down);
+        assert!(health.error.is_some());
+    }
+
     #[tokio::test]
     async fn get_returns_none_for_missing_key() {
         let disk_dir = TempDir::new().unwrap();
@@ -0,0 +1,310 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::info;
+
+use crate::cache::{CacheBackend, CacheEntry, CacheKey, CacheStats};
+use crate::config::CachePolicy;
+
+const DEFAULT_SHARDS: usize = 16;
+/// Room kept per shard beyond `max_object_size` for the key and content type an entry is
+/// also charged for.
+const ENTRY_HEADROOM: u64 = 4096;
+
+/// In-process LRU cache, sharded by key hash.
+///
+/// Every entry is charged for its key, content type and payload, so `max_memory` is an
+/// exact bound on the bytes held.
+pub struct MemoryCache {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    inserts: AtomicU64,
+}
+
+struct Shard {
+    capacity: u64,
+    used: u64,
+    tick: u64,
+    entries: HashMap<CacheKey, Slot>,
+    recency: BTreeMap<u64, CacheKey>,
+}
+
+struct Slot {
+    entry: CacheEntry,
+    charge: u64,
+    tick: u64,
+}
+
+impl MemoryCache {
+    pub fn new(policy: &CachePolicy) -> Result<MemoryCache, anyhow::Error> {
+        Self::with_shards(policy, DEFAULT_SHARDS)
+    }
+
+    pub fn with_shards(policy: &CachePolicy, shards: usize) -> Result<MemoryCache, anyhow::Error> {
+        let max_bytes_memory = policy.max_memory.as_u64();
+        if max_bytes_memory == 0 {
+            return Err(anyhow!("Bad policy: 0 max_bytes_memory"));
+        }
+        // Fewer, larger shards when needed, so that any object up to `max_object_size` fits in
+        // one. A size of 0 means objects up to `max_memory`, as in the handler.
+        let max_object = match policy.max_object_size.as_u64() {
+            0 => max_bytes_memory,
+            size => size.min(max_bytes_memory),
+        };
+        let max_shards = max_bytes_memory / (max_object + ENTRY_HEADROOM);
+        let shards = shards.clamp(1, max_shards.max(1) as usize);
+
+        let per_shard = max_bytes_memory / shards as u64;
+        // Hand the remainder to the first shards so capacities sum to max_memory exactly.
+        let remainder = max_bytes_memory % shards as u64;
+        let shards = (0..shards as u64)
+            .map(|index| {
+                Mutex::new(Shard {
+                    capacity: per_shard + u64::from(index < remainder),
+                    used: 0,
+                    tick: 0,
+                    entries: HashMap::new(),
+                    recency: BTreeMap::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            memory_capacity_bytes = max_bytes_memory,
+            shards = shards.len(),
+            "In-memory LRU cache initialized"
+        );
+
+        Ok(Self {
+            shards,
+            hasher: RandomState::new(),
+            inserts: AtomicU64::new(0),
+        })
+    }
+
+    fn shard(&self, key: &CacheKey) -> &Mutex<Shard> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+fn charge(key: &CacheKey, entry: &CacheEntry) -> u64 {
+    let content_type = entry.content_type.as_deref().map_or(0, str::len);
+    (key.bucket_id.len() + key.path.len() + content_type + entry.bytes.len()) as u64
+}
+
+impl Shard {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<CacheEntry> {
+        let tick = self.next_tick();
+        let slot = self.entries.get_mut(key)?;
+        self.recency.remove(&slot.tick);
+        slot.tick = tick;
+        self.recency.insert(tick, key.clone());
+        Some(slot.entry.clone())
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(slot) = self.entries.remove(key) {
+            self.recency.remove(&slot.tick);
+            self.used -= slot.charge;
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, entry: CacheEntry) -> bool {
+        let charge = charge(&key, &entry);
+        self.remove(&key);
+        if charge > self.capacity {
+            return false;
+        }
+
+        while self.used + charge > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(slot) = self.entries.remove(&oldest) {
+                self.used -= slot.charge;
+            }
+        }
+
+        let tick = self.next_tick();
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(
+            key,
+            Slot {
+                entry,
+                charge,
+                tick,
+            },
+        );
+        self.used += charge;
+        true
+    }
+}
+
+#[async_trait]
+impl CacheBackend for MemoryCache {
+    #[tracing::instrument(skip(self))]
+    async fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let mut shard = self.shard(key).lock().expect("cache shard poisoned");
+        shard.get(key)
+    }
+
+    #[tracing::instrument(skip(self, bytes, content_type))]
+    async fn put(
+        &self,
+        key: CacheKey,
+        bytes: Bytes,
+        content_type: Option<String>,
+    ) -> Option<String> {
+        let entry = CacheEntry::new(bytes, content_type);
+        let inserted = {
+            let mut shard = self.shard(&key).lock().expect("cache shard poisoned");
+            shard.insert(key, entry)
+        };
+        if inserted {
+            self.inserts.fetch_add(1, Ordering::Relaxed);
+        }
+        None
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn stats(&self) -> CacheStats {
+        let bytes = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().expect("cache shard poisoned").used)
+            .sum();
+        CacheStats {
+            inserts: self.inserts.load(Ordering::Relaxed),
+            bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use bytesize::ByteSize;
+
+    use super::*;
+
+    fn make_policy(max_memory_bytes: u64) -> CachePolicy {
+        CachePolicy {
+            max_memory: ByteSize(max_memory_bytes),
+            max_object_size: ByteSize(max_memory_bytes),
+            max_disk: ByteSize(0),
+            disk_path: None,
+            dedup: false,
+            backend: Default::default(),
+            remote: None,
+        }
+    }
+
+    fn key(path: &str) -> CacheKey {
+        CacheKey::new("b".to_string(), path.to_string())
+    }
+
+    #[test]
+    fn new_rejects_zero_max_memory() {
+        assert!(MemoryCache::new(&make_policy(0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn put_and_get_roundtrip() {
+        let cache = MemoryCache::new(&make_policy(1024)).unwrap();
+        let data = Bytes::from_static(b"hello world");
+
+        cache
+            .put(key("a.txt"), data.clone(), Some("text/plain".to_string()))
+            .await;
+
+        let entry = cache.get(&key("a.txt")).await.unwrap();
+        assert_eq!(entry.bytes, data);
+        assert_eq!(entry.content_type.as_deref(), Some("text/plain"));
+    }
+
+    #[tokio::test]
+    async fn accounts_exact_bytes() {
+        let cache = MemoryCache::with_shards(&make_policy(1024), 1).unwrap();
+        cache
+            .put(
+                key("a"),
+                Bytes::from_static(b"12345"),
+                Some("x/y".to_string()),
+            )
+            .await;
+        // "b" + "a" + "x/y" + 5 payload bytes
+        assert_eq!(cache.stats().await.bytes, 10);
+
+        cache.put(key("a"), Bytes::from_static(b"1"), None).await;
+        assert_eq!(cache.stats().await.bytes, 3);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        // Each entry charges 1 + 1 + 8 = 10 bytes.
+        let cache = MemoryCache::with_shards(&make_policy(20), 1).unwrap();
+        let data = Bytes::from_static(b"01234567");
+
+        cache.put(key("a"), data.clone(), None).await;
+        cache.put(key("b"), data.clone(), None).await;
+        assert!(cache.get(&key("a")).await.is_some());
+
+        cache.put(key("c"), data.clone(), None).await;
+        assert!(cache.get(&key("a")).await.is_some());
+        assert!(cache.get(&key("b")).await.is_none());
+        assert!(cache.get(&key("c")).await.is_some());
+        assert_eq!(cache.stats().await.bytes, 20);
+    }
+
+    #[tokio::test]
+    async fn shards_fit_max_object_size() {
+        let policy = CachePolicy {
+            max_object_size: ByteSize(0),
+            ..make_policy(1024 * 1024)
+        };
+        let cache = MemoryCache::new(&policy).unwrap();
+        assert_eq!(cache.shards.len(), 1);
+        cache
+            .put(key("a"), Bytes::from(vec![0u8; 512 * 1024]), None)
+            .await;
+        assert!(cache.get(&key("a")).await.is_some());
+
+        let policy = CachePolicy {
+            max_object_size: ByteSize(64 * 1024),
+            ..make_policy(1024 * 1024)
+        };
+        let cache = MemoryCache::new(&policy).unwrap();
+        assert_eq!(cache.shards.len(), 15);
+        for index in 0..32 {
+            let path = format!("albums/{index}/cover.jpg");
+            cache
+                .put(
+                    key(&path),
+                    Bytes::from(vec![0u8; 64 * 1024]),
+                    Some("image/jpeg".to_string()),
+                )
+                .await;
+            assert!(cache.get(&key(&path)).await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_entries_larger_than_shard() {
+        let cache = MemoryCache::with_shards(&make_policy(8), 1).unwrap();
+        cache
+            .put(key("a"), Bytes::from_static(b"0123456789"), None)
+            .await;
+        assert!(cache.get(&key("a")).await.is_none());
+        assert_eq!(cache.stats().await.inserts, 0);
+    }
+}
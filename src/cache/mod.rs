@@ -3,12 +3,19 @@ use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
+use crate::ready::ProbeStatus;
+
+pub mod dedup;
 pub mod foyer;
+pub mod memory;
+pub mod remote;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
     pub bytes: Bytes,
     pub content_type: Option<String>,
+    #[serde(skip)]
+    pub digest: Option<String>,
 }
 
 impl CacheEntry {
@@ -16,30 +23,68 @@ impl CacheEntry {
         Self {
             bytes,
             content_type,
+            digest: None,
         }
     }
+
+    pub fn with_digest(mut self, digest: Option<String>) -> Self {
+        self.digest = digest;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheKey {
     pub bucket_id: String,
     pub path: String,
+    /// Store generation the entry was filled from, so a reloaded store never serves old entries.
+    #[serde(default)]
+    pub generation: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct CacheStats {
     pub inserts: u64,
+    pub bytes: u64,
+}
+
+/// Local cache condition reported by `/ready`. Never critical: misses fall through to origin.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheHealth {
+    pub status: ProbeStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CacheHealth {
+    pub fn ok() -> Self {
+        Self {
+            status: ProbeStatus::Ok,
+            error: None,
+        }
+    }
 }
 
 impl CacheKey {
     pub fn new(bucket_id: String, path: String) -> Self {
-        Self { bucket_id, path }
+        Self {
+            bucket_id,
+            path,
+            generation: 0,
+        }
+    }
+
+    pub fn with_generation(mut self, generation: u64) -> Self {
+        self.generation = generation;
+        self
     }
 }
 
 impl PartialEq for CacheKey {
     fn eq(&self, other: &Self) -> bool {
-        self.bucket_id == other.bucket_id && self.path == other.path
+        self.bucket_id == other.bucket_id
+            && self.path == other.path
+            && self.generation == other.generation
     }
 }
 
@@ -49,12 +94,22 @@ impl Hash for CacheKey {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.bucket_id.hash(state);
         self.path.hash(state);
+        self.generation.hash(state);
     }
 }
 
 #[async_trait]
 pub trait CacheBackend: Send + Sync {
     async fn get(&self, key: &CacheKey) -> Option<CacheEntry>;
-    async fn put(&self, key: CacheKey, bytes: Bytes, content_type: Option<String>);
+    /// Stores an entry, returning the content digest if the backend computed one.
+    async fn put(
+        &self,
+        key: CacheKey,
+        bytes: Bytes,
+        content_type: Option<String>,
+    ) -> Option<String>;
     async fn stats(&self) -> CacheStats;
+    async fn health(&self) -> CacheHealth {
+        CacheHealth::ok()
+    }
 }
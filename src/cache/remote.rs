@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::FutureExt;
+use futures::future::Shared;
+use object_store::path::Path;
+use object_store::{Attribute, Attributes, ObjectStore, ObjectStoreExt, PutOptions, PutPayload};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::cache::{CacheEntry, CacheKey};
+use crate::config::ReadinessConfig;
+use crate::metrics::Metrics;
+use crate::ready::{ProbeReport, ReadinessProbe};
+
+/// Shared cache tier backed by an object store, checked between the local cache and origin.
+///
+/// Objects are laid out as `{prefix}/{bucket_id}/{path}` so several cachegate nodes can
+/// share one bucket or filesystem path. The content type travels as object metadata where
+/// the store supports it.
+pub struct RemoteTier {
+    store: Arc<dyn ObjectStore>,
+    prefix: Option<String>,
+    metrics: Arc<Metrics>,
+    readiness: ReadinessProbe,
+    /// Cleared once the store rejects attributes, e.g. a local filesystem.
+    attributes: AtomicBool,
+    /// Completion of the latest background write per location, so writes land in call order.
+    pending: Mutex<HashMap<Path, Shared<oneshot::Receiver<()>>>>,
+}
+
+impl RemoteTier {
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Option<String>, metrics: Arc<Metrics>) -> Self {
+        let prefix = prefix
+            .map(|value| value.trim_matches('/').to_string())
+            .filter(|value| !value.is_empty());
+        Self {
+            store,
+            prefix,
+            metrics,
+            // A lost tier only costs hit rate, so it never fails readiness.
+            readiness: ReadinessProbe::new(&ReadinessConfig {
+                critical: false,
+                ..Default::default()
+            }),
+            attributes: AtomicBool::new(true),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn health(&self) -> ProbeReport {
+        self.readiness.check(self.store.as_ref()).await
+    }
+
+    fn location(&self, key: &CacheKey) -> Path {
+        match &self.prefix {
+            Some(prefix) => Path::from(format!("{prefix}/{}/{}", key.bucket_id, key.path)),
+            None => Path::from(format!("{}/{}", key.bucket_id, key.path)),
+        }
+    }
+
+    /// Returns the object if the tier holds it. Errors are logged and treated as misses.
+    pub async fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let location = self.location(key);
+        let result = match self.store.get(&location).await {
+            Ok(result) => {
+                let content_type = result
+                    .attributes
+                    .get(&Attribute::ContentType)
+                    .map(|value| value.to_string());
+                result
+                    .bytes()
+                    .await
+                    .map(|bytes| CacheEntry::new(bytes, content_type))
+            }
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(entry) => {
+                self.metrics.inc_remote_tier("hit");
+                Some(entry)
+            }
+            Err(object_store::Error::NotFound { .. }) => {
+                self.metrics.inc_remote_tier("miss");
+                None
+            }
+            Err(err) => {
+                self.metrics.inc_remote_tier("error");
+                warn!(
+                    bucket_id = %key.bucket_id,
+                    path = %key.path,
+                    error = %err,
+                    "remote tier get failed"
+                );
+                None
+            }
+        }
+    }
+
+    /// Writes the object back to the tier in the background.
+    pub fn spawn_put(self: &Arc<Self>, key: CacheKey, bytes: Bytes, content_type: Option<String>) {
+        self.spawn_ordered(key, move |tier, key, location| async move {
+            let size = bytes.len();
+            match tier.put(&location, bytes, content_type).await {
+                Ok(()) => {
+                    tier.metrics.inc_remote_tier_write("ok");
+                    info!(
+                        bucket_id = %key.bucket_id,
+                        path = %key.path,
+                        size,
+                        "remote tier write-back completed"
+                    );
+                }
+                Err(err) => {
+                    tier.metrics.inc_remote_tier_write("error");
+                    warn!(
+                        bucket_id = %key.bucket_id,
+                        path = %key.path,
+                        error = %err,
+                        "remote tier write-back failed"
+                    );
+                }
+            }
+        });
+    }
+
+    /// Drops a copy that an overwrite has made stale.
+    pub fn spawn_delete(self: &Arc<Self>, key: CacheKey) {
+        self.spawn_ordered(key, |tier, key, location| async move {
+            match tier.store.delete(&location).await {
+                Ok(_) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(err) => {
+                    tier.metrics.inc_remote_tier_write("error");
+                    warn!(
+                        bucket_id = %key.bucket_id,
+                        path = %key.path,
+                        error = %err,
+                        "remote tier invalidation failed"
+                    );
+                }
+            }
+        });
+    }
+
+    async fn put(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        content_type: Option<String>,
+    ) -> object_store::Result<()> {
+        if let Some(content_type) = content_type
+            && self.attributes.load(Ordering::Relaxed)
+        {
+            let mut attributes = Attributes::new();
+            attributes.insert(Attribute::ContentType, content_type.into());
+            let options = PutOptions {
+                attributes,
+                ..Default::default()
+            };
+            match self
+                .store
+                .put_opts(location, PutPayload::from(bytes.clone()), options)
+                .await
+            {
+                Err(object_store::Error::NotImplemented { .. }) => {
+                    self.attributes.store(false, Ordering::Relaxed);
+                }
+                result => return result.map(|_| ()),
+            }
+        }
+        self.store
+            .put(location, PutPayload::from(bytes))
+            .await
+            .map(|_| ())
+    }
+
+    /// Runs `op` once every write spawned earlier for the same location has finished.
+    fn spawn_ordered<F, Fut>(self: &Arc<Self>, key: CacheKey, op: F)
+    where
+        F: FnOnce(Arc<Self>, CacheKey, Path) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let tier = Arc::clone(self);
+        let location = self.location(&key);
+        let (done, finished) = oneshot::channel();
+        let finished = finished.shared();
+        let previous = self
+            .pending
+            .lock()
+            .unwrap()
+            .insert(location.clone(), finished.clone());
+        tokio::spawn(async move {
+            if let Some(previous) = previous {
+                // Cancelled just means the earlier task is gone, which is as good as done.
+                let _ = previous.await;
+            }
+            op(Arc::clone(&tier), key, location.clone()).await;
+            let mut pending = tier.pending.lock().unwrap();
+            if pending
+                .get(&location)
+                .is_some_and(|latest| latest.ptr_eq(&finished))
+            {
+                pending.remove(&location);
+            }
+            drop(pending);
+            let _ = done.send(());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    async fn eventually<T>(mut check: impl AsyncFnMut() -> Option<T>) -> Option<T> {
+        for _ in 0..50 {
+            if let Some(value) = check().await {
+                return Some(value);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn write_back_then_get() {
+        let store = Arc::new(InMemory::new());
+        let tier = Arc::new(RemoteTier::new(
+            store.clone(),
+            Some("/shared/".to_string()),
+            Arc::new(Metrics::new()),
+        ));
+        let key = CacheKey::new("bucket".to_string(), "dir/a.txt".to_string());
+
+        assert!(tier.get(&key).await.is_none());
+
+        tier.spawn_put(
+            key.clone(),
+            Bytes::from_static(b"payload"),
+            Some("text/x-custom".to_string()),
+        );
+        let found = eventually(async || tier.get(&key).await).await.unwrap();
+        assert_eq!(found.bytes.as_ref(), b"payload");
+        assert_eq!(found.content_type.as_deref(), Some("text/x-custom"));
+
+        let raw = store
+            .get(&Path::from("shared/bucket/dir/a.txt"))
+            .await
+            .unwrap();
+        assert_eq!(raw.bytes().await.unwrap().as_ref(), b"payload");
+    }
+
+    #[tokio::test]
+    async fn writes_to_one_key_apply_in_order() {
+        let store = Arc::new(InMemory::new());
+        let tier = Arc::new(RemoteTier::new(store, None, Arc::new(Metrics::new())));
+        let key = CacheKey::new("bucket".to_string(), "a.txt".to_string());
+
+        for round in 0..20 {
+            tier.spawn_put(key.clone(), Bytes::from(vec![0u8; 64 * 1024]), None);
+            tier.spawn_delete(key.clone());
+            let drained =
+                eventually(async || tier.pending.lock().unwrap().is_empty().then_some(()));
+            assert!(drained.await.is_some(), "round {round} never drained");
+            assert!(
+                tier.get(&key).await.is_none(),
+                "round {round} kept a stale copy"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_attributes_are_unsupported() {
+        let root = tempfile::tempdir().unwrap();
+        let store =
+            Arc::new(object_store::local::LocalFileSystem::new_with_prefix(root.path()).unwrap());
+        let tier = Arc::new(RemoteTier::new(store, None, Arc::new(Metrics::new())));
+        let key = CacheKey::new("bucket".to_string(), "a.txt".to_string());
+
+        tier.spawn_put(
+            key.clone(),
+            Bytes::from_static(b"local"),
+            Some("text/plain".to_string()),
+        );
+        let found = eventually(async || tier.get(&key).await).await.unwrap();
+        assert_eq!(found.bytes.as_ref(), b"local");
+        assert_eq!(found.content_type, None);
+    }
+}
@@ -1,27 +1,159 @@
 use anyhow::Context;
 use bytesize::ByteSize;
-use serde::Deserialize;
+use ipnet::IpNet;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Config {
     pub listen: String,
-    pub stores: HashMap<String, StoreConfig>,
+    pub stores: HashMap<String, StoreEntry>,
     pub auth: AuthConfig,
     pub cache: CachePolicy,
     #[serde(default)]
     pub sentry: Option<SentryConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct AuthConfig {
-    pub public_key: String,
-    pub private_key: String,
+    /// Key pair for presign payloads without a `kid`.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    /// Keys selected by the payload `kid`, for rotation.
+    #[serde(default)]
+    pub keys: Vec<AuthKeyConfig>,
+    /// Unscoped token, treated as a token named `default`.
     #[serde(default)]
     pub bearer_token: Option<String>,
+    #[serde(default)]
+    pub bearer_tokens: Vec<BearerTokenConfig>,
+    /// Accept OIDC-issued JWTs as bearer tokens.
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    /// Peers whose `X-Forwarded-For` is trusted when checking a presign `ip` constraint.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+    /// Where uses of `max_uses` URLs are counted. Only applied on restart.
+    #[serde(default)]
+    pub replay: ReplayConfig,
+    /// Longest `ttl` accepted by `presign` and `POST /_sign`.
+    #[serde(default = "default_max_sign_ttl", with = "humantime_serde")]
+    pub max_sign_ttl: Duration,
+}
+
+fn default_max_sign_ttl() -> Duration {
+    Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+/// A named bearer token. Empty scope lists allow everything.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BearerTokenConfig {
+    pub name: String,
+    /// Plaintext token. Set either this or `sha256`.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Hex SHA-256 of the token, to keep it out of the config.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    /// Path prefixes, matched on whole segments.
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JwtConfig {
+    /// JWKS endpoint, refetched every `refresh_interval` and on unknown `kid`s.
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// Local JWKS file, read on startup and reload.
+    #[serde(default)]
+    pub jwks_file: Option<String>,
+    pub issuer: String,
+    pub audience: Vec<String>,
+    /// Accepted `alg`s. Defaults to the RSA, ECDSA and EdDSA algorithms.
+    #[serde(default)]
+    pub algorithms: Option<Vec<jsonwebtoken::Algorithm>>,
+    /// Clock skew allowed on `exp` and `nbf`.
+    #[serde(default = "default_jwt_leeway", with = "humantime_serde")]
+    pub leeway: Duration,
+    #[serde(default = "default_jwks_refresh", with = "humantime_serde")]
+    pub refresh_interval: Duration,
+    /// Claim logged as the token name.
+    #[serde(default = "default_subject_claim")]
+    pub subject_claim: String,
+    /// A token may do what any matching rule allows; with no rules it is denied.
+    #[serde(default)]
+    pub rules: Vec<JwtRuleConfig>,
+}
+
+fn default_jwt_leeway() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_jwks_refresh() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_subject_claim() -> String {
+    "sub".to_string()
+}
+
+/// Grants a scope to tokens whose `claim` holds `value`. Without `claim` it applies to all.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JwtRuleConfig {
+    #[serde(default)]
+    pub claim: Option<String>,
+    /// Matched against a string claim, a word of a space-separated one (`scope`), or an
+    /// element of an array claim.
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ReplayConfig {
+    #[serde(default)]
+    pub backend: ReplayBackendKind,
+    /// Use log for the `file` backend.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayBackendKind {
+    #[default]
+    Memory,
+    File,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AuthKeyConfig {
+    pub kid: String,
+    pub public_key: String,
+    /// Omit to keep a retired key verify-only.
+    #[serde(default)]
+    pub private_key: Option<String>,
+    /// RFC 3339 time after which URLs signed with this key are rejected.
+    #[serde(default, with = "humantime_serde")]
+    pub not_after: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct CachePolicy {
     #[serde(with = "bytesize_serde")]
     pub max_memory: ByteSize,
@@ -33,9 +165,53 @@ pub struct CachePolicy {
     pub max_disk: ByteSize,
     #[serde(default)]
     pub disk_path: Option<String>,
+    #[serde(default)]
+    pub dedup: bool,
+    #[serde(default)]
+    pub backend: CacheBackendKind,
+    #[serde(default)]
+    pub remote: Option<RemoteTierConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RemoteTierConfig {
+    pub store: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackendKind {
+    #[default]
+    Foyer,
+    Memory,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MirrorStrategy {
+    /// Try members one after another, falling through on misses and errors.
+    #[default]
+    Ordered,
+    /// Ask every member at once and serve the first success.
+    Parallel,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationPolicy {
+    /// Every target must accept the write before the PUT succeeds.
+    #[default]
+    All,
+    /// Only the primary is awaited; replicas are written in the background.
+    Async,
+    /// The PUT succeeds once `quorum` targets accepted the write. The primary must be one of
+    /// them, since reads are served from it.
+    Quorum,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct SentryConfig {
     pub dsn: String,
     pub environment: Option<String>,
@@ -43,22 +219,304 @@ pub struct SentryConfig {
     pub debug: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(tag = "type")]
+/// A store's backend keys and shared options, side by side in one mapping.
+///
+/// Deserialized by hand rather than with two `flatten`s, which would accept any unknown key:
+/// the option keys are split off and both halves then reject keys they do not know.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreEntry {
+    pub backend: StoreConfig,
+    pub options: StoreOptions,
+}
+
+impl<'de> Deserialize<'de> for StoreEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut backend = serde_yaml::Mapping::deserialize(deserializer)?;
+        let mut options = serde_yaml::Mapping::new();
+        for field in STORE_OPTION_FIELDS {
+            if let Some(value) = backend.remove(*field) {
+                options.insert((*field).into(), value);
+            }
+        }
+        Ok(Self {
+            backend: StoreConfig::deserialize(serde_yaml::Value::Mapping(backend))
+                .map_err(D::Error::custom)?,
+            options: StoreOptions::deserialize(serde_yaml::Value::Mapping(options))
+                .map_err(D::Error::custom)?,
+        })
+    }
+}
+
+/// Keys of `StoreOptions`, split off a store entry before the backend sees it.
+const STORE_OPTION_FIELDS: &[&str] = &[
+    "prefix",
+    "rewrite",
+    "read_only",
+    "write_only",
+    "disable_prefetch",
+    "client",
+    "retry",
+    "concurrency",
+    "breaker",
+    "replication",
+    "readiness",
+    "bandwidth",
+];
+
+/// Settings shared by every store type.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoreOptions {
+    /// Key prefix the bucket is mounted at; requests cannot reach keys outside it.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Request path rewrites, applied before the prefix. The first matching rule wins.
+    #[serde(default)]
+    pub rewrite: Vec<RewriteRule>,
+    /// Reject PUT. Mutually exclusive with `write_only`.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Reject GET and HEAD, e.g. for ingestion buckets.
+    #[serde(default)]
+    pub write_only: bool,
+    /// Ignore `HEAD ?prefetch=true` and serve a plain HEAD.
+    #[serde(default)]
+    pub disable_prefetch: bool,
+    /// HTTP client settings for network-backed stores. Unset fields keep library defaults.
+    #[serde(default)]
+    pub client: UpstreamClientConfig,
+    #[serde(default)]
+    pub retry: UpstreamRetryConfig,
+    #[serde(default)]
+    pub concurrency: Option<ConcurrencyConfig>,
+    #[serde(default)]
+    pub breaker: Option<BreakerConfig>,
+    #[serde(default)]
+    pub replication: Option<ReplicationConfig>,
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+    #[serde(default)]
+    pub bandwidth: Option<BandwidthConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    /// Upstream requests allowed in flight at once for the store. Must be at least 1.
+    pub max_inflight: NonZeroUsize,
+    /// How long a request may wait for a slot before failing with 503.
+    #[serde(default = "default_queue_timeout", with = "humantime_serde")]
+    pub queue_timeout: Duration,
+}
+
+fn default_queue_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BreakerConfig {
+    /// Fraction of failed upstream requests within `window` that opens the breaker.
+    #[serde(default = "default_failure_rate")]
+    pub failure_rate: f64,
+    /// Requests needed within `window` before the failure rate is considered.
+    #[serde(default = "default_min_requests")]
+    pub min_requests: u32,
+    #[serde(default = "default_breaker_window", with = "humantime_serde")]
+    pub window: Duration,
+    /// How long the breaker stays open before letting a probe request through.
+    #[serde(default = "default_breaker_window", with = "humantime_serde")]
+    pub open_for: Duration,
+}
+
+fn default_failure_rate() -> f64 {
+    0.5
+}
+
+fn default_min_requests() -> u32 {
+    20
+}
+
+fn default_breaker_window() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// Upstream bandwidth cap, shared by reads and uploads.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BandwidthConfig {
+    #[serde(with = "bytesize_serde")]
+    pub bytes_per_sec: ByteSize,
+    /// Bytes that may go through at once after an idle period. Defaults to one second's worth.
+    #[serde(default, with = "option_bytesize")]
+    pub burst: Option<ByteSize>,
+}
+
+mod option_bytesize {
+    use bytesize::ByteSize;
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<ByteSize>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "bytesize_serde")] ByteSize);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(value)| value))
+    }
+}
+
+/// How `/ready` probes a store.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadinessConfig {
+    /// Key to HEAD, which must exist. Without one the probe lists the store root.
+    #[serde(default)]
+    pub canary: Option<String>,
+    /// Whether the store being down makes `/ready` fail.
+    #[serde(default = "default_critical")]
+    pub critical: bool,
+    /// How long a probe result is reused, which also bounds how often the store is probed.
+    #[serde(default = "default_probe_interval", with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(default = "default_probe_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            canary: None,
+            critical: default_critical(),
+            interval: default_probe_interval(),
+            timeout: default_probe_timeout(),
+        }
+    }
+}
+
+fn default_critical() -> bool {
+    true
+}
+
+fn default_probe_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_probe_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Extra stores every write is copied to. Reads still come from the primary.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicationConfig {
+    pub replicas: Vec<StoreConfig>,
+    #[serde(default)]
+    pub policy: ReplicationPolicy,
+    /// Targets that must accept a write under the `quorum` policy. Defaults to a majority.
+    #[serde(default)]
+    pub quorum: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamClientConfig {
+    #[serde(default, with = "humantime_serde")]
+    pub connect_timeout: Option<Duration>,
+    /// Per-request timeout, covering the whole response body.
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub pool_idle_timeout: Option<Duration>,
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(default)]
+    pub http2_only: Option<bool>,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// Comma-separated hosts that bypass the proxy.
+    #[serde(default)]
+    pub proxy_excludes: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamRetryConfig {
+    #[serde(default)]
+    pub max_retries: Option<usize>,
+    /// Total time budget across all attempts of one request.
+    #[serde(default, with = "humantime_serde")]
+    pub retry_timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub init_backoff: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_backoff: Option<Duration>,
+    #[serde(default)]
+    pub backoff_base: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteRule {
+    pub pattern: String,
+    /// Replacement with `$1` / `$name` capture references.
+    pub template: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
 pub enum StoreConfig {
     #[serde(rename = "s3")]
     S3 {
         bucket: String,
         region: String,
-        access_key: String,
-        secret_key: String,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+        session_token: Option<String>,
         endpoint: Option<String>,
         allow_http: Option<bool>,
+        role_arn: Option<String>,
+        role_session_name: Option<String>,
+        web_identity_token_file: Option<String>,
+        sts_endpoint: Option<String>,
+        metadata_endpoint: Option<String>,
     },
     #[serde(rename = "azure")]
     Azure {
         container: String,
-        connection_string: String,
+        connection_string: Option<String>,
+        account: Option<String>,
+        sas_token: Option<String>,
+        use_emulator: Option<bool>,
+        client_id: Option<String>,
+        tenant_id: Option<String>,
+        federated_token_file: Option<String>,
+        msi_endpoint: Option<String>,
+    },
+    #[serde(rename = "gcs")]
+    Gcs {
+        bucket: String,
+        service_account_key: Option<String>,
+        service_account_path: Option<String>,
+        endpoint: Option<String>,
+        skip_signature: Option<bool>,
+    },
+    #[serde(rename = "filesystem")]
+    Filesystem { root: String },
+    #[serde(rename = "memory")]
+    Memory { seed_dir: Option<String> },
+    #[serde(rename = "http")]
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        allow_http: Option<bool>,
+    },
+    #[serde(rename = "mirror")]
+    Mirror {
+        members: Vec<StoreConfig>,
+        #[serde(default)]
+        strategy: MirrorStrategy,
     },
 }
 
@@ -74,3 +532,85 @@ pub fn load_from_env() -> anyhow::Result<Config> {
             .context("failed to parse config from environment variables")
     }
 }
+
+/// Where the config was loaded from, kept so it can be re-read on reload.
+#[derive(Debug, Clone)]
+pub enum ConfigSource {
+    Env,
+    File(String),
+}
+
+impl ConfigSource {
+    pub fn load(&self) -> anyhow::Result<Config> {
+        match self {
+            Self::Env => load_from_env().context("failed to load config from env"),
+            Self::File(path) => {
+                let raw = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read config file {path}"))?;
+                serde_yaml::from_str(&raw).context("failed to parse config file")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_entry_splits_backend_and_options() {
+        let entry: StoreEntry = serde_yaml::from_str(
+            "type: filesystem\nroot: /srv\nprefix: tenant/\nrewrite:\n  - pattern: a\n    template: b\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entry.backend,
+            StoreConfig::Filesystem {
+                root: "/srv".to_string()
+            }
+        );
+        assert_eq!(entry.options.prefix.as_deref(), Some("tenant/"));
+        assert_eq!(entry.options.rewrite.len(), 1);
+    }
+
+    #[test]
+    fn store_option_fields_match_store_options() {
+        // Fails to compile when a field is added, as a reminder to list it.
+        let StoreOptions {
+            prefix: _,
+            rewrite: _,
+            read_only: _,
+            write_only: _,
+            disable_prefetch: _,
+            client: _,
+            retry: _,
+            concurrency: _,
+            breaker: _,
+            replication: _,
+            readiness: _,
+            bandwidth: _,
+        } = StoreOptions::default();
+        assert_eq!(STORE_OPTION_FIELDS.len(), 12);
+
+        for field in STORE_OPTION_FIELDS {
+            let err = serde_yaml::from_str::<StoreOptions>(&format!("{field}: 0"))
+                .map(|_| String::new())
+                .unwrap_or_else(|err| err.to_string());
+            assert!(!err.contains("unknown field"), "{field}: {err}");
+        }
+    }
+
+    #[test]
+    fn store_entry_rejects_unknown_keys() {
+        for yaml in [
+            "type: filesystem\nroot: /srv\nprefx: tenant/\n",
+            "type: filesystem\nroot: /srv\nrewrite:\n  - pattern: a\n    template: b\n    strip_prefx: c\n",
+            "type: filesystem\nroot: /srv\nretry:\n  max_retry: 3\n",
+            "type: mirror\nmembers:\n  - type: filesystem\n    root: /srv\n    bucket: x\n",
+        ] {
+            let err = serde_yaml::from_str::<StoreEntry>(yaml).unwrap_err();
+            assert!(err.to_string().contains("unknown field"), "{yaml}: {err}");
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use arc_swap::ArcSwap;
 use axum::Json;
 use axum::body::Body;
-use axum::extract::{Extension, FromRequestParts, Path, Query, State};
+use axum::extract::{ConnectInfo, Extension, FromRequestParts, Path, Query, State};
 use axum::http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header};
 use axum::middleware::Next;
 use axum::response::IntoResponse;
@@ -11,26 +13,37 @@ use bytes::Bytes;
 use bytes::BytesMut;
 use futures::StreamExt;
 use object_store::ObjectStoreExt;
-use object_store::WriteMultipart;
+use object_store::{GetResult, WriteMultipart};
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{info, info_span, warn};
 
-use crate::auth::{AuthContext, AuthError, AuthMethod, AuthState};
-use crate::cache::{CacheBackend, CacheEntry, CacheKey};
+use crate::auth::{
+    AuthContext, AuthError, AuthMethod, AuthState, SignRequest, SignedUrl, UploadLimits,
+};
+use crate::cache::remote::RemoteTier;
+use crate::cache::{CacheBackend, CacheEntry, CacheHealth, CacheKey};
 use crate::inflight::{Inflight, InflightPermit};
 use crate::metrics::{Metrics, UpstreamErrorKind};
-use crate::store::StoreMap;
+use crate::ready::{ProbeReport, ProbeStatus};
+use crate::reload::{ConfigDiff, Reloader};
+use crate::replay::ReplayStore;
+use crate::store::guard::{GuardStatus, Rejection, UpstreamPermit};
+use crate::store::{Store, StoreMap};
 
 pub type InflightResult = Result<CacheEntry, AppError>;
 
 pub struct AppState<C: CacheBackend> {
-    pub stores: StoreMap,
-    pub auth: AuthState,
+    /// Swapped as a whole on config reload; load once per request.
+    pub stores: ArcSwap<StoreMap>,
+    pub auth: ArcSwap<AuthState>,
     pub cache: Arc<C>,
+    pub remote: Option<Arc<RemoteTier>>,
     pub inflight: Arc<Inflight<InflightResult>>,
     pub metrics: Arc<Metrics>,
     pub cache_max_object_bytes: u64,
+    pub reloader: Reloader,
+    pub replay: Arc<dyn ReplayStore>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -61,6 +74,7 @@ pub async fn auth_middleware<C: CacheBackend + 'static>(
         path = %path,
         method = %method,
         auth = tracing::field::Empty,
+        token = tracing::field::Empty,
         status = tracing::field::Empty,
         error = tracing::field::Empty
     );
@@ -68,19 +82,36 @@ pub async fn auth_middleware<C: CacheBackend + 'static>(
 
     let bearer_token = parse_bearer_token(&parts.headers);
     let mut auth_method = None;
+    let mut token_name = None;
+    let mut limits = UploadLimits::default();
+    let mut pending_use = None;
     let mut last_error: Option<AuthError> = None;
+    let auth = state.auth.load_full();
 
     if let Some(token) = bearer_token.as_deref() {
-        match state.auth.verify_bearer(token) {
-            Ok(_) => auth_method = Some(AuthMethod::Bearer),
+        match authorize_bearer(&auth, &state.metrics, token, &method, &bucket_id, &path).await {
+            Ok((method, name)) => {
+                auth_method = Some(method);
+                token_name = Some(name);
+            }
             Err(err) => last_error = Some(err),
         }
     }
 
     if auth_method.is_none() {
         if let Some(sig) = params.get("sig") {
-            match state.auth.verify(&method, &bucket_id, &path, sig) {
-                Ok(_) => auth_method = Some(AuthMethod::Presign),
+            let peer = parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip());
+            let forwarded_for = forwarded_for(&parts.headers);
+            let client_ip = auth.client_ip(peer, forwarded_for.as_deref());
+            match auth.verify(&method, &bucket_id, &path, client_ip, sig) {
+                Ok(grant) => {
+                    auth_method = Some(AuthMethod::Presign);
+                    limits = grant.limits;
+                    pending_use = grant.uses;
+                }
                 Err(err) => last_error = Some(err),
             }
         } else if last_error.is_none() {
@@ -93,21 +124,101 @@ pub async fn auth_middleware<C: CacheBackend + 'static>(
         None => {
             state.metrics.inc_auth_fail(method.as_str());
             let error = last_error.unwrap_or(AuthError::MissingAuth);
-            span.record("status", StatusCode::UNAUTHORIZED.to_string());
             span.record("error", error.to_string());
             warn!(bucket_id = %bucket_id, path = %path, error = %error, "auth failed");
+            if let AuthError::JwksUnavailable(_) = &error {
+                span.record("status", StatusCode::SERVICE_UNAVAILABLE.to_string());
+                return Err(AppError::service_unavailable("jwks unavailable"));
+            }
+            // A valid token used outside its scope is authenticated, just not authorized.
+            if let AuthError::TokenOutOfScope(name) = &error {
+                span.record("token", name.as_str());
+                span.record("status", StatusCode::FORBIDDEN.to_string());
+                return Err(AppError::forbidden("token not allowed for this request"));
+            }
+            span.record("status", StatusCode::UNAUTHORIZED.to_string());
             return Err(AppError::unauthorized("invalid auth"));
         }
     };
 
     span.record("auth", auth_method.as_str());
+    if let Some(name) = token_name.as_deref() {
+        span.record("token", name);
+    }
+
+    if let Some(store) = state.stores.load().get(&bucket_id)
+        && !store.policy.allows(&parts.method)
+    {
+        span.record("status", StatusCode::METHOD_NOT_ALLOWED.to_string());
+        warn!(bucket_id = %bucket_id, path = %path, method = %method, "method not allowed by store policy");
+        return Err(AppError::method_not_allowed("method not allowed for store")
+            .with_allow(store.policy.allowed_methods()));
+    }
+
+    // Spent only once the request is otherwise allowed, and given back if it fails.
+    if let Some(uses) = pending_use.as_ref() {
+        let within_uses = state
+            .replay
+            .consume(&uses.key, uses.max_uses, uses.expires_at)
+            .await
+            .map_err(|err| {
+                warn!(bucket_id = %bucket_id, path = %path, error = %format!("{err:#}"), "replay store failed");
+                AppError::service_unavailable("replay store unavailable")
+            })?;
+        if !within_uses {
+            state.metrics.inc_auth_fail(method.as_str());
+            span.record("error", AuthError::UsesExhausted.to_string());
+            span.record("status", StatusCode::GONE.to_string());
+            warn!(bucket_id = %bucket_id, path = %path, "presigned url uses exhausted");
+            return Err(AppError::gone("presigned url uses exhausted"));
+        }
+    }
+
     span.record("status", StatusCode::OK.to_string());
 
     let mut request = Request::from_parts(parts, body);
     request.extensions_mut().insert(AuthContext {
         method: auth_method,
+        token: token_name,
+        limits,
     });
-    Ok(next.run(request).await)
+    let response = next.run(request).await;
+    if let Some(uses) = pending_use
+        && !response.status().is_success()
+        && let Err(err) = state.replay.refund(&uses.key).await
+    {
+        warn!(bucket_id = %bucket_id, path = %path, error = %format!("{err:#}"), "replay refund failed");
+    }
+    Ok(response)
+}
+
+/// Checks a bearer value as a configured token, then as a JWT, returning the method and name.
+async fn authorize_bearer(
+    auth: &AuthState,
+    metrics: &Metrics,
+    token: &str,
+    method: &str,
+    bucket_id: &str,
+    path: &str,
+) -> Result<(AuthMethod, String), AuthError> {
+    match auth.verify_bearer(token, method, bucket_id, path) {
+        Ok(name) => {
+            metrics.inc_bearer(&name, "ok");
+            Ok((AuthMethod::Bearer, name))
+        }
+        // Not one of ours; it may still be a JWT from the configured issuer.
+        Err(AuthError::InvalidBearer | AuthError::BearerNotConfigured) if auth.accepts_jwt() => {
+            auth.verify_jwt(token, method, bucket_id, path)
+                .await
+                .map(|subject| (AuthMethod::Jwt, subject))
+        }
+        Err(err) => {
+            if let AuthError::TokenOutOfScope(name) = &err {
+                metrics.inc_bearer(name, "denied");
+            }
+            Err(err)
+        }
+    }
 }
 
 pub async fn get_object<C: CacheBackend + 'static>(
@@ -122,6 +233,7 @@ pub async fn get_object<C: CacheBackend + 'static>(
         bucket_id = %bucket_id,
         path = %path,
         auth = tracing::field::Empty,
+        token = tracing::field::Empty,
         cache = tracing::field::Empty,
         inflight = tracing::field::Empty,
         status = tracing::field::Empty,
@@ -131,7 +243,10 @@ pub async fn get_object<C: CacheBackend + 'static>(
     let _enter = span.enter();
 
     span.record("auth", auth.method.as_str());
-    let key = CacheKey::new(bucket_id.clone(), path.clone());
+    if let Some(token) = auth.token.as_deref() {
+        span.record("token", token);
+    }
+    let key = cache_key(&state, &bucket_id, &path);
     let mut response_bytes: Option<usize> = None;
 
     let result = 'request: {
@@ -225,6 +340,7 @@ pub async fn head_object<C: CacheBackend + 'static>(
         bucket_id = %bucket_id,
         path = %path,
         auth = tracing::field::Empty,
+        token = tracing::field::Empty,
         cache = tracing::field::Empty,
         inflight = tracing::field::Empty,
         status = tracing::field::Empty,
@@ -234,8 +350,17 @@ pub async fn head_object<C: CacheBackend + 'static>(
     let _enter = span.enter();
 
     span.record("auth", auth.method.as_str());
-    let key = CacheKey::new(bucket_id.clone(), path.clone());
-    let prefetch_enabled = parse_prefetch(&params);
+    if let Some(token) = auth.token.as_deref() {
+        span.record("token", token);
+    }
+    let key = cache_key(&state, &bucket_id, &path);
+    // Stores with prefetch disabled serve a plain HEAD, so clients can prefetch across stores.
+    let prefetch_enabled = parse_prefetch(&params)
+        && state
+            .stores
+            .load()
+            .get(&bucket_id)
+            .is_none_or(|store| store.policy.prefetch);
     let mut response_bytes: Option<usize> = None;
 
     let result = 'request: {
@@ -261,14 +386,19 @@ pub async fn head_object<C: CacheBackend + 'static>(
             span.record("inflight", "skipped");
         }
 
-        let store = state.stores.get(&bucket_id).ok_or_else(|| {
+        let stores = state.stores.load_full();
+        let store = stores.get(&bucket_id).ok_or_else(|| {
             warn!(bucket_id = %bucket_id, path = %path, "unknown bucket");
             AppError::not_found("unknown bucket")
         })?;
-        let location: object_store::path::Path = path.as_str().into();
+        let location = store
+            .location(&path)
+            .ok_or_else(|| AppError::bad_request("invalid object path"))?;
+        let permit = acquire_upstream(store, &bucket_id, &path).await?;
         let head_start = Instant::now();
-        let meta = match store.head(&location).await {
+        let meta = match store.object_store.head(&location).await {
             Ok(meta) => {
+                permit.record_ok();
                 state
                     .metrics
                     .observe_upstream_latency_ms(method, head_start.elapsed().as_millis() as u64);
@@ -276,6 +406,7 @@ pub async fn head_object<C: CacheBackend + 'static>(
                 meta
             }
             Err(err) => {
+                permit.record_err(&err);
                 let error_kind = UpstreamErrorKind::from_store_error(&err);
                 state
                     .metrics
@@ -340,6 +471,7 @@ pub async fn put_object<C: CacheBackend + 'static>(
         bucket_id = %bucket_id,
         path = %path,
         auth = tracing::field::Empty,
+        token = tracing::field::Empty,
         cache = tracing::field::Empty,
         status = tracing::field::Empty,
         bytes = tracing::field::Empty,
@@ -348,7 +480,10 @@ pub async fn put_object<C: CacheBackend + 'static>(
     let _enter = span.enter();
 
     span.record("auth", auth.method.as_str());
-    let key = CacheKey::new(bucket_id.clone(), path.clone());
+    if let Some(token) = auth.token.as_deref() {
+        span.record("token", token);
+    }
+    let key = cache_key(&state, &bucket_id, &path);
     let mut response_bytes: Option<usize> = None;
 
     let result = 'request: {
@@ -356,14 +491,44 @@ pub async fn put_object<C: CacheBackend + 'static>(
             break 'request Err(AppError::bad_request("invalid object path"));
         }
 
-        let store = state.stores.get(&bucket_id).ok_or_else(|| {
+        let limits = &auth.limits;
+        let request_content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        if !limits.allows_content_type(request_content_type) {
+            warn!(bucket_id = %bucket_id, path = %path, content_type = ?request_content_type, "content type not allowed by signature");
+            break 'request Err(AppError::unsupported_media_type("content type not allowed"));
+        }
+        // Reject a declared length up front; the stream is still checked below.
+        if let Some(declared) = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+        {
+            if limits.max_len.is_some_and(|max| declared > max) {
+                break 'request Err(AppError::payload_too_large("body exceeds signed max_len"));
+            }
+            if limits.min_len.is_some_and(|min| declared < min) {
+                break 'request Err(AppError::bad_request("body shorter than signed min_len"));
+            }
+        }
+
+        let stores = state.stores.load_full();
+        let store = stores.get(&bucket_id).ok_or_else(|| {
             warn!(bucket_id = %bucket_id, path = %path, "unknown bucket");
             AppError::not_found("unknown bucket")
         })?;
 
-        let location: object_store::path::Path = path.as_str().into();
+        let location = store
+            .location(&path)
+            .ok_or_else(|| AppError::bad_request("invalid object path"))?;
 
-        match store.head(&location).await {
+        let permit = match acquire_upstream(store, &bucket_id, &path).await {
+            Ok(permit) => permit,
+            Err(err) => break 'request Err(err),
+        };
+
+        match store.object_store.head(&location).await {
             Ok(_) => {
                 warn!(bucket_id = %bucket_id, path = %path, "overwriting existing object");
             }
@@ -375,9 +540,10 @@ pub async fn put_object<C: CacheBackend + 'static>(
         }
 
         let upload_start = Instant::now();
-        let upload = match store.put_multipart(&location).await {
+        let upload = match store.object_store.put_multipart(&location).await {
             Ok(upload) => upload,
             Err(err) => {
+                permit.record_err(&err);
                 let error_kind = UpstreamErrorKind::from_store_error(&err);
                 state
                     .metrics
@@ -400,6 +566,7 @@ pub async fn put_object<C: CacheBackend + 'static>(
         let cap_bytes = state.cache_max_object_bytes as usize;
         let mut buffer = BytesMut::new();
         let mut capped = cap_bytes == 0;
+        let mut digest = None;
 
         while let Some(chunk) = stream.next().await {
             let chunk = match chunk {
@@ -412,6 +579,11 @@ pub async fn put_object<C: CacheBackend + 'static>(
             };
 
             total_bytes = total_bytes.saturating_add(chunk.len());
+            if limits.max_len.is_some_and(|max| total_bytes as u64 > max) {
+                let _ = write.abort().await;
+                warn!(bucket_id = %bucket_id, path = %path, bytes = total_bytes, "upload aborted; body exceeds signed max_len");
+                break 'request Err(AppError::payload_too_large("body exceeds signed max_len"));
+            }
 
             if !capped {
                 let remaining = cap_bytes.saturating_sub(buffer.len());
@@ -425,17 +597,28 @@ pub async fn put_object<C: CacheBackend + 'static>(
                 }
             }
 
+            if let Some(throttle) = store.throttle.as_ref() {
+                throttle.consume(chunk.len(), "write").await;
+            }
             write.put(chunk);
         }
 
+        if limits.min_len.is_some_and(|min| (total_bytes as u64) < min) {
+            let _ = write.abort().await;
+            warn!(bucket_id = %bucket_id, path = %path, bytes = total_bytes, "upload aborted; body shorter than signed min_len");
+            break 'request Err(AppError::bad_request("body shorter than signed min_len"));
+        }
+
         match write.finish().await {
             Ok(_result) => {
+                permit.record_ok();
                 state
                     .metrics
                     .observe_upstream_latency_ms(method, upload_start.elapsed().as_millis() as u64);
                 state.metrics.inc_upstream_ok(method);
             }
             Err(err) => {
+                permit.record_err(&err);
                 let error_kind = UpstreamErrorKind::from_store_error(&err);
                 state
                     .metrics
@@ -454,10 +637,22 @@ pub async fn put_object<C: CacheBackend + 'static>(
 
         response_bytes = Some(total_bytes);
 
+        if let Some(remote) = state.remote.as_ref() {
+            if capped {
+                remote.spawn_delete(key.clone());
+            } else {
+                remote.spawn_put(
+                    key.clone(),
+                    buffer.clone().freeze(),
+                    content_type_from_headers(&headers, &path),
+                );
+            }
+        }
+
         if !capped {
             let content_type = content_type_from_headers(&headers, &path);
             span.record("cache", "insert");
-            state
+            digest = state
                 .cache
                 .put(key, buffer.freeze(), content_type.clone())
                 .await;
@@ -472,7 +667,7 @@ pub async fn put_object<C: CacheBackend + 'static>(
             );
         }
 
-        break 'request Ok(build_put_response());
+        break 'request Ok(build_put_response(digest));
     };
 
     span.record("elapsed_ms", start.elapsed().as_millis().to_string());
@@ -502,17 +697,43 @@ async fn fetch_and_cache_entry<C: CacheBackend>(
     path: &str,
     method: &str,
 ) -> Result<CacheEntry, AppError> {
-    let store = state.stores.get(bucket_id).ok_or_else(|| {
+    let stores = state.stores.load_full();
+    let store = stores.get(bucket_id).ok_or_else(|| {
         warn!(bucket_id = %bucket_id, path = %path, "unknown bucket");
         AppError::not_found("unknown bucket")
     })?;
 
-    let location: object_store::path::Path = path.into();
+    let location = store
+        .location(path)
+        .ok_or_else(|| AppError::bad_request("invalid object path"))?;
 
     let start = Instant::now();
-    let result = match store.get(&location).await {
+    if let Some(remote) = state.remote.as_ref()
+        && let Some(entry) = remote.get(key).await
+    {
+        info!(
+            bucket_id = %bucket_id,
+            path = %path,
+            size = entry.bytes.len(),
+            elapsed_ms = start.elapsed().as_millis(),
+            "served from remote tier"
+        );
+        return Ok(cache_fetched_entry(
+            state,
+            key,
+            bucket_id,
+            path,
+            entry.bytes,
+            entry.content_type,
+        )
+        .await);
+    }
+
+    let permit = acquire_upstream(store, bucket_id, path).await?;
+    let result = match store.object_store.get(&location).await {
         Ok(result) => result,
         Err(err) => {
+            permit.record_err(&err);
             let error_kind = UpstreamErrorKind::from_store_error(&err);
             state
                 .metrics
@@ -529,9 +750,10 @@ async fn fetch_and_cache_entry<C: CacheBackend>(
         }
     };
 
-    let bytes = match result.bytes().await {
+    let bytes = match read_upstream(store, result).await {
         Ok(bytes) => bytes,
         Err(err) => {
+            permit.record_err(&err);
             let error_kind = UpstreamErrorKind::from_store_error(&err);
             state
                 .metrics
@@ -548,15 +770,73 @@ async fn fetch_and_cache_entry<C: CacheBackend>(
         }
     };
 
+    permit.record_ok();
     state
         .metrics
         .observe_upstream_latency_ms(method, start.elapsed().as_millis() as u64);
     state.metrics.inc_upstream_ok(method);
 
-    let content_type = Some(resolve_content_type(path, &bytes));
     let elapsed_ms = start.elapsed().as_millis();
+    let entry = cache_fetched_entry(state, key, bucket_id, path, bytes, None).await;
+    if let Some(remote) = state.remote.as_ref() {
+        remote.spawn_put(key.clone(), entry.bytes.clone(), entry.content_type.clone());
+    }
+
+    let span = tracing::Span::current();
+    span.record("bytes", entry.bytes.len().to_string());
+    info!(
+        bucket_id = %bucket_id,
+        path = %path,
+        size = entry.bytes.len(),
+        elapsed_ms,
+        content_type = %entry.content_type.as_deref().unwrap_or("application/octet-stream"),
+        "cache miss fetch"
+    );
+    Ok(entry)
+}
+
+/// Collects an upstream body, pacing it through the store's bandwidth limit if it has one.
+async fn read_upstream(store: &Store, result: GetResult) -> object_store::Result<Bytes> {
+    let Some(throttle) = store.throttle.as_ref() else {
+        return result.bytes().await;
+    };
+    let mut buffer = BytesMut::with_capacity(result.meta.size as usize);
+    let mut stream = result.into_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        throttle.consume(chunk.len(), "read").await;
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(buffer.freeze())
+}
+
+async fn acquire_upstream<'a>(
+    store: &'a Store,
+    bucket_id: &str,
+    path: &str,
+) -> Result<UpstreamPermit<'a>, AppError> {
+    store.guard.acquire().await.map_err(|rejection| {
+        warn!(
+            bucket_id = %bucket_id,
+            path = %path,
+            rejection = ?rejection,
+            "upstream request rejected"
+        );
+        AppError::from_rejection(rejection)
+    })
+}
+
+async fn cache_fetched_entry<C: CacheBackend>(
+    state: &AppState<C>,
+    key: &CacheKey,
+    bucket_id: &str,
+    path: &str,
+    bytes: Bytes,
+    content_type: Option<String>,
+) -> CacheEntry {
+    let content_type = content_type.or_else(|| Some(resolve_content_type(path, &bytes)));
     let cap_bytes = state.cache_max_object_bytes as usize;
-    if cap_bytes == 0 || bytes.len() > cap_bytes {
+    let digest = if cap_bytes == 0 || bytes.len() > cap_bytes {
         info!(
             bucket_id = %bucket_id,
             path = %path,
@@ -564,24 +844,15 @@ async fn fetch_and_cache_entry<C: CacheBackend>(
             cap_bytes,
             "cache skipped; object exceeds cap"
         );
+        None
     } else {
         state
             .cache
             .put(key.clone(), bytes.clone(), content_type.clone())
-            .await;
-    }
+            .await
+    };
 
-    let span = tracing::Span::current();
-    span.record("bytes", bytes.len().to_string());
-    info!(
-        bucket_id = %bucket_id,
-        path = %path,
-        size = bytes.len(),
-        elapsed_ms,
-        content_type = %content_type.as_deref().unwrap_or("application/octet-stream"),
-        "cache miss fetch"
-    );
-    Ok(CacheEntry::new(bytes, content_type))
+    CacheEntry::new(bytes, content_type).with_digest(digest)
 }
 
 fn resolve_content_type(path: &str, bytes: &Bytes) -> String {
@@ -627,21 +898,238 @@ pub async fn stats<C: CacheBackend + 'static>(
         upstream_err_total: snapshot.upstream_err_total,
         cache: CacheStatsResponse {
             entries: cache_stats.inserts,
-            bytes: 0,
+            bytes: cache_stats.bytes,
         },
     }))
 }
 
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+    stores: HashMap<String, GuardStatus>,
+}
+
 pub async fn health() -> Result<Response<Body>, AppError> {
     let mut response = Response::new(Body::from("OK"));
     *response.status_mut() = StatusCode::OK;
     Ok(response)
 }
 
+/// Stays 200 while a breaker is open; `status` turns `degraded` instead.
+pub async fn store_health<C: CacheBackend + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+) -> Result<Json<HealthResponse>, AppError> {
+    let stores: HashMap<String, GuardStatus> = state
+        .stores
+        .load()
+        .iter()
+        .map(|(id, store)| (id.clone(), store.guard.status()))
+        .filter(|(_, status)| status.breaker.is_some() || status.max_inflight.is_some())
+        .collect();
+    let degraded = stores
+        .values()
+        .any(|status| status.breaker.is_some_and(|breaker| breaker != "closed"));
+
+    Ok(Json(HealthResponse {
+        status: if degraded { "degraded" } else { "ok" },
+        stores,
+    }))
+}
+
+const MAX_SIGN_BATCH: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct SignBody {
+    method: String,
+    bucket: String,
+    paths: Vec<String>,
+    #[serde(with = "humantime_serde")]
+    ttl: Duration,
+    /// Sign v2 prefix grants instead of exact paths.
+    #[serde(default)]
+    prefix: bool,
+    #[serde(default)]
+    base_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignResponse {
+    urls: Vec<SignedUrl>,
+}
+
+/// Presigns URLs for a batch of paths. The caller's token must itself be allowed each
+/// request it signs, so a scoped token cannot mint wider access.
+pub async fn sign_urls<C: CacheBackend + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    headers: HeaderMap,
+    Json(body): Json<SignBody>,
+) -> Result<Json<SignResponse>, AppError> {
+    let token =
+        parse_bearer_token(&headers).ok_or_else(|| AppError::unauthorized("invalid auth"))?;
+    let method = body.method.to_uppercase();
+    if !matches!(method.as_str(), "GET" | "HEAD" | "PUT") {
+        return Err(AppError::bad_request("method must be GET, HEAD or PUT"));
+    }
+    if body.paths.is_empty() || body.paths.len() > MAX_SIGN_BATCH {
+        return Err(AppError::bad_request(&format!(
+            "paths must hold 1 to {MAX_SIGN_BATCH} entries"
+        )));
+    }
+    // A prefix grant covers whole segments, so `albums/123/` and `albums/123` are the same grant.
+    let paths: Vec<&str> = body
+        .paths
+        .iter()
+        .map(|path| {
+            if body.prefix {
+                path.trim_end_matches('/')
+            } else {
+                path.as_str()
+            }
+        })
+        .collect();
+    if body.ttl.is_zero() || paths.iter().any(|path| path.is_empty()) {
+        return Err(AppError::bad_request("ttl and paths must not be empty"));
+    }
+
+    let auth = state.auth.load_full();
+    for path in &paths {
+        if let Err(error) =
+            authorize_bearer(&auth, &state.metrics, &token, &method, &body.bucket, path).await
+        {
+            warn!(bucket_id = %body.bucket, path = %path, error = %error, "sign auth failed");
+            return Err(match error {
+                AuthError::TokenOutOfScope(_) => {
+                    AppError::forbidden("token not allowed for this request")
+                }
+                AuthError::JwksUnavailable(_) => AppError::service_unavailable("jwks unavailable"),
+                _ => {
+                    state.metrics.inc_auth_fail("POST");
+                    AppError::unauthorized("invalid auth")
+                }
+            });
+        }
+    }
+    // Only after authenticating, so bucket ids are not revealed to anonymous callers.
+    if !state.stores.load().contains_key(&body.bucket) {
+        return Err(AppError::not_found("unknown bucket"));
+    }
+
+    let mut urls = Vec::with_capacity(paths.len());
+    for path in paths {
+        let request = SignRequest {
+            method: method.clone(),
+            bucket_id: body.bucket.clone(),
+            path: path.to_string(),
+            ttl: body.ttl,
+            prefix: body.prefix,
+        };
+        let signed =
+            auth.sign(&request, body.base_url.as_deref())
+                .map_err(|error| match error {
+                    AuthError::TtlTooLong(_) => AppError::bad_request(&error.to_string()),
+                    _ => {
+                        warn!(bucket_id = %body.bucket, error = %error, "signing failed");
+                        AppError::service_unavailable(&error.to_string())
+                    }
+                })?;
+        urls.push(signed);
+    }
+    Ok(Json(SignResponse { urls }))
+}
+
+/// Re-reads the config; bearer-token only, since presigned URLs are scoped to objects.
+pub async fn reload_config<C: CacheBackend + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    headers: HeaderMap,
+) -> Result<Json<ConfigDiff>, AppError> {
+    let token =
+        parse_bearer_token(&headers).ok_or_else(|| AppError::unauthorized("invalid auth"))?;
+    if let Err(error) = state.auth.load().verify_admin_bearer(&token) {
+        state.metrics.inc_auth_fail("POST");
+        warn!(error = %error, "reload auth failed");
+        return Err(AppError::unauthorized("invalid auth"));
+    }
+
+    let diff = state
+        .reloader
+        .reload(&state)
+        .await
+        .map_err(|err| AppError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message: format!("reload rejected: {err:#}"),
+            retry_after: None,
+            allow: None,
+        })?;
+    Ok(Json(diff))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadyResponse {
+    status: &'static str,
+    stores: HashMap<String, ProbeReport>,
+    cache: CacheReadiness,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheReadiness {
+    local: CacheHealth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote: Option<ProbeReport>,
+}
+
+/// Probes every store at once; only a critical store being down makes this a 503.
+pub async fn ready<C: CacheBackend + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+) -> (StatusCode, Json<ReadyResponse>) {
+    let stores = state.stores.load_full();
+    let probes = stores.iter().map(|(id, store)| async move {
+        let report = store.readiness.check(store.object_store.as_ref()).await;
+        (id.clone(), report)
+    });
+    let remote = async {
+        match state.remote.as_ref() {
+            Some(remote) => Some(remote.health().await),
+            None => None,
+        }
+    };
+    let (stores, local, remote) = futures::join!(
+        futures::future::join_all(probes),
+        state.cache.health(),
+        remote
+    );
+    let stores: HashMap<String, ProbeReport> = stores.into_iter().collect();
+
+    let ready = !stores
+        .values()
+        .any(|report| report.critical && report.status == ProbeStatus::Down);
+    for (id, report) in &stores {
+        if report.status == ProbeStatus::Down {
+            warn!(store_id = %id, error = ?report.error, critical = report.critical, "store not ready");
+        }
+    }
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadyResponse {
+            status: if ready { "ready" } else { "not_ready" },
+            stores,
+            cache: CacheReadiness { local, remote },
+        }),
+    )
+}
+
 pub async fn metrics<C: CacheBackend + 'static>(
     State(state): State<Arc<AppState<C>>>,
 ) -> Result<Response<Body>, AppError> {
     let _cache_stats = state.cache.stats().await;
+    for store in state.stores.load().values() {
+        store.guard.refresh_breaker();
+    }
     let body = state.metrics.render_prometheus();
     let mut response = Response::new(Body::from(body));
     response.headers_mut().insert(
@@ -651,9 +1139,20 @@ pub async fn metrics<C: CacheBackend + 'static>(
     Ok(response)
 }
 
+/// Keys entries by the store's generation, so entries filled before a reload are never served.
+fn cache_key<C: CacheBackend>(state: &AppState<C>, bucket_id: &str, path: &str) -> CacheKey {
+    let generation = state
+        .stores
+        .load()
+        .get(bucket_id)
+        .map_or(0, |store| store.generation);
+    CacheKey::new(bucket_id.to_string(), path.to_string()).with_generation(generation)
+}
+
 fn build_response(entry: CacheEntry, cache_hit: bool) -> Response<Body> {
     let bytes = entry.bytes;
     let content_type = entry.content_type;
+    let digest = entry.digest;
     let length = bytes.len();
 
     let mut response = Response::new(Body::from(bytes));
@@ -669,6 +1168,7 @@ fn build_response(entry: CacheEntry, cache_hit: bool) -> Response<Body> {
     if let Ok(value) = HeaderValue::from_str(cache_status) {
         headers.insert("X-CG-Status", value);
     }
+    insert_digest_header(headers, digest.as_deref());
     let len_value = HeaderValue::from_str(&length.to_string())
         .unwrap_or_else(|_| HeaderValue::from_static("0"));
     headers.insert(header::CONTENT_LENGTH, len_value);
@@ -689,6 +1189,7 @@ fn build_head_response(entry: CacheEntry) -> Response<Body> {
     {
         headers.insert(header::CONTENT_TYPE, value);
     }
+    insert_digest_header(headers, entry.digest.as_deref());
     let len_value = HeaderValue::from_str(&length.to_string())
         .unwrap_or_else(|_| HeaderValue::from_static("0"));
     headers.insert(header::CONTENT_LENGTH, len_value);
@@ -713,12 +1214,21 @@ fn build_head_response_with_meta(length: u64, content_type: Option<String>) -> R
     response
 }
 
-fn build_put_response() -> Response<Body> {
+fn build_put_response(digest: Option<String>) -> Response<Body> {
     let mut response = Response::new(Body::empty());
     *response.status_mut() = StatusCode::OK;
+    insert_digest_header(response.headers_mut(), digest.as_deref());
     response
 }
 
+fn insert_digest_header(headers: &mut HeaderMap, digest: Option<&str>) {
+    if let Some(digest) = digest
+        && let Ok(value) = HeaderValue::from_str(&format!("sha256:{digest}"))
+    {
+        headers.insert("X-CG-Digest", value);
+    }
+}
+
 fn content_type_from_headers(headers: &HeaderMap, path: &str) -> Option<String> {
     if let Some(value) = headers.get(header::CONTENT_TYPE)
         && let Ok(value) = value.to_str()
@@ -808,6 +1318,16 @@ fn spawn_head_prefetch<C: CacheBackend + 'static>(
     });
 }
 
+/// Joins repeated `X-Forwarded-For` headers into one hop list.
+fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    (!hops.is_empty()).then(|| hops.join(","))
+}
+
 fn parse_bearer_token(headers: &HeaderMap) -> Option<String> {
     let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
     let mut parts = value.split_whitespace();
@@ -824,6 +1344,9 @@ fn parse_bearer_token(headers: &HeaderMap) -> Option<String> {
 pub struct AppError {
     status: StatusCode,
     message: String,
+    retry_after: Option<Duration>,
+    /// Methods listed in the `Allow` header of a 405.
+    allow: Option<String>,
 }
 
 impl AppError {
@@ -831,6 +1354,8 @@ impl AppError {
         Self {
             status: StatusCode::BAD_REQUEST,
             message: message.to_string(),
+            retry_after: None,
+            allow: None,
         }
     }
 
@@ -838,6 +1363,8 @@ impl AppError {
         Self {
             status: StatusCode::UNAUTHORIZED,
             message: message.to_string(),
+            retry_after: None,
+            allow: None,
         }
     }
 
@@ -845,15 +1372,94 @@ impl AppError {
         Self {
             status: StatusCode::NOT_FOUND,
             message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn forbidden(message: &str) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn method_not_allowed(message: &str) -> Self {
+        Self {
+            status: StatusCode::METHOD_NOT_ALLOWED,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn with_allow(mut self, allow: String) -> Self {
+        self.allow = Some(allow);
+        self
+    }
+
+    fn payload_too_large(message: &str) -> Self {
+        Self {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn unsupported_media_type(message: &str) -> Self {
+        Self {
+            status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn gone(message: &str) -> Self {
+        Self {
+            status: StatusCode::GONE,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn service_unavailable(message: &str) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: message.to_string(),
+            retry_after: None,
+            allow: None,
+        }
+    }
+
+    fn from_rejection(rejection: Rejection) -> Self {
+        let message = match rejection {
+            Rejection::Saturated { .. } => "upstream saturated",
+            Rejection::Open { .. } => "upstream circuit open",
+        };
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: message.to_string(),
+            retry_after: Some(rejection.retry_after()),
+            allow: None,
         }
     }
 
     fn from_store(error: object_store::Error) -> Self {
         match error {
             object_store::Error::NotFound { .. } => Self::not_found("object not found"),
+            object_store::Error::NotSupported { .. } => {
+                Self::method_not_allowed("operation not supported by store")
+            }
             _ => Self {
                 status: StatusCode::BAD_GATEWAY,
                 message: "upstream error".to_string(),
+                retry_after: None,
+                allow: None,
             },
         }
     }
@@ -863,6 +1469,18 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response<Body> {
         let mut response = Response::new(Body::from(self.message));
         *response.status_mut() = self.status;
+        if let Some(retry_after) = self.retry_after {
+            // Round up so clients never retry before the breaker or queue can admit them.
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        if let Some(allow) = self.allow
+            && let Ok(value) = HeaderValue::from_str(&allow)
+        {
+            response.headers_mut().insert(header::ALLOW, value);
+        }
         response
     }
 }
@@ -8,13 +8,14 @@ use tracing_error::ErrorLayer;
 use tracing_subscriber::fmt;
 
 use anyhow::Context;
+use arc_swap::ArcSwap;
 use axum::Router;
 use axum::middleware;
-use axum::routing::get;
+use axum::routing::{get, post};
 use base64::Engine;
 use clap::Parser;
 use serde::Serialize;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt};
 use tracing_subscriber::{Layer, Registry};
 
@@ -24,15 +25,23 @@ mod config;
 mod handler;
 mod inflight;
 mod metrics;
+mod ready;
+mod reload;
+mod replay;
 mod store;
 
-use auth::AuthState;
+use auth::{AuthState, SignRequest};
 use cache::CacheBackend;
+use cache::dedup::DedupCache;
 use cache::foyer::FoyerCache;
-use config::{Config, load_from_env};
+use cache::memory::MemoryCache;
+use cache::remote::RemoteTier;
+use config::{CacheBackendKind, Config, ConfigSource};
 use handler::AppState;
 use inflight::Inflight;
 use metrics::Metrics;
+use reload::Reloader;
+use replay::build_replay_store;
 use store::build_stores;
 
 #[derive(Debug, Parser)]
@@ -47,6 +56,8 @@ struct Args {
 #[derive(Debug, clap::Subcommand)]
 enum Command {
     Keygen(KeygenArgs),
+    /// Print presigned URLs using the private key from `--config`.
+    Presign(PresignArgs),
 }
 
 #[derive(Debug, Parser)]
@@ -57,18 +68,29 @@ struct KeygenArgs {
     force: bool,
 }
 
-#[derive(Debug)]
-enum ConfigSource {
-    Env,
-    File(String),
+#[derive(Debug, Parser)]
+struct PresignArgs {
+    #[arg(long)]
+    bucket: String,
+    /// Repeat to sign several paths.
+    #[arg(long, required = true)]
+    path: Vec<String>,
+    #[arg(long, default_value = "GET", ignore_case = true, value_parser = ["GET", "HEAD", "PUT"])]
+    method: String,
+    #[arg(long, default_value = "15m", value_parser = humantime::parse_duration)]
+    ttl: std::time::Duration,
+    /// Sign a v2 grant for every key below `path`.
+    #[arg(long)]
+    prefix: bool,
+    /// Prepended to the printed URLs, e.g. `https://cdn.example.com`.
+    #[arg(long)]
+    base_url: Option<String>,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    if let Some(command) = args.command {
-        return match command {
-            Command::Keygen(command_args) => run_keygen(command_args),
-        };
+    if let Some(Command::Keygen(command_args)) = args.command {
+        return run_keygen(command_args);
     }
     let source = match args.config.as_deref() {
         Some("env") => ConfigSource::Env,
@@ -77,15 +99,11 @@ fn main() -> anyhow::Result<()> {
             "config source must be specified with --config, either 'env' or a file path"
         ),
     };
+    if let Some(Command::Presign(command_args)) = args.command {
+        return run_presign(&source, command_args);
+    }
 
-    let config: Config = match source {
-        ConfigSource::Env => load_from_env().context("failed to load config from env")?,
-        ConfigSource::File(path) => {
-            let raw = std::fs::read_to_string(&path)
-                .with_context(|| format!("failed to read config file {path}"))?;
-            serde_yaml::from_str(&raw).context("failed to parse config file")?
-        }
-    };
+    let config = source.load()?;
 
     let sentry_guard = init_sentry(&config);
     init_tracing(sentry_guard.is_some());
@@ -93,7 +111,7 @@ fn main() -> anyhow::Result<()> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
-    if let Err(err) = runtime.block_on(async_main(config)) {
+    if let Err(err) = runtime.block_on(async_main(source, config)) {
         error!(error = %err, "cachegate failed to start");
         return Err(err);
     }
@@ -130,6 +148,28 @@ fn run_keygen(args: KeygenArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn run_presign(source: &ConfigSource, args: PresignArgs) -> anyhow::Result<()> {
+    let config = source.load()?;
+    anyhow::ensure!(
+        config.stores.contains_key(&args.bucket),
+        "unknown bucket {}",
+        args.bucket
+    );
+    let auth = AuthState::from_config(&config.auth).context("failed to initialize auth")?;
+    for path in args.path {
+        let request = SignRequest {
+            method: args.method.clone(),
+            bucket_id: args.bucket.clone(),
+            path,
+            ttl: args.ttl,
+            prefix: args.prefix,
+        };
+        let signed = auth.sign(&request, args.base_url.as_deref())?;
+        println!("{}", signed.url);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize)]
 struct AuthKeyYaml {
     auth: AuthKeyPair,
@@ -141,33 +181,112 @@ struct AuthKeyPair {
     private_key: String,
 }
 
-async fn async_main(config: Config) -> anyhow::Result<()> {
-    let auth = AuthState::from_config(&config.auth).context("failed to initialize auth")?;
-    let stores = build_stores(&config.stores).context("failed to build stores")?;
-
+async fn async_main(source: ConfigSource, config: Config) -> anyhow::Result<()> {
     let metrics = Arc::new(Metrics::new());
 
-    let registry = metrics.registry();
+    match config.cache.backend {
+        CacheBackendKind::Foyer => {
+            let cache = FoyerCache::new(config.cache.clone(), metrics.registry())
+                .await
+                .context("Failed to foyer cache")?;
+            serve_with_cache(source, config, metrics, cache).await
+        }
+        CacheBackendKind::Memory => {
+            let cache = MemoryCache::new(&config.cache).context("Failed to build memory cache")?;
+            serve_with_cache(source, config, metrics, cache).await
+        }
+    }
+}
+
+async fn serve_with_cache<C: CacheBackend + 'static>(
+    source: ConfigSource,
+    config: Config,
+    metrics: Arc<Metrics>,
+    cache: C,
+) -> anyhow::Result<()> {
+    if config.cache.dedup {
+        info!("content-hash deduplication enabled");
+        serve(source, config, metrics, DedupCache::new(cache)).await
+    } else {
+        serve(source, config, metrics, cache).await
+    }
+}
+
+async fn serve<C: CacheBackend + 'static>(
+    source: ConfigSource,
+    config: Config,
+    metrics: Arc<Metrics>,
+    cache: C,
+) -> anyhow::Result<()> {
+    let auth = AuthState::from_config(&config.auth).context("failed to initialize auth")?;
+    let replay =
+        build_replay_store(&config.auth.replay).context("failed to initialize replay store")?;
+    let mut stores = build_stores(&config.stores, metrics.clone())
+        .await
+        .context("failed to build stores")?;
+    let remote = match config.cache.remote.as_ref() {
+        Some(remote) => {
+            // The tier's store is reserved for cache data and is not routable.
+            let store = stores
+                .remove(&remote.store)
+                .with_context(|| format!("remote tier references unknown store {}", remote.store))?
+                .object_store
+                .clone();
+            info!(store_id = %remote.store, "remote cache tier enabled");
+            Some(Arc::new(RemoteTier::new(
+                store,
+                remote.prefix.clone(),
+                metrics.clone(),
+            )))
+        }
+        None => None,
+    };
+
     let cache_max_object_bytes = if config.cache.max_object_size.as_u64() == 0 {
         config.cache.max_memory.as_u64()
     } else {
         config.cache.max_object_size.as_u64()
     };
-    let state = AppState::<FoyerCache> {
-        stores,
-        auth,
-        cache: Arc::new(
-            FoyerCache::new(config.cache.clone(), registry)
-                .await
-                .context("Failed to foyer cache")?,
-        ),
+    let listen = config.listen.clone();
+    let state = AppState::<C> {
+        stores: ArcSwap::from_pointee(stores),
+        auth: ArcSwap::from_pointee(auth),
+        cache: Arc::new(cache),
+        remote,
         inflight: Arc::new(Inflight::new()),
-        metrics: metrics.clone(),
+        metrics,
         cache_max_object_bytes,
+        reloader: Reloader::new(source, config),
+        replay,
+    };
+    let state = Arc::new(state);
+    spawn_reload_on_hangup(state.clone());
+    run_server(state, listen).await
+}
+
+#[cfg(unix)]
+fn spawn_reload_on_hangup<C: CacheBackend + 'static>(state: Arc<AppState<C>>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            warn!(error = %err, "failed to install SIGHUP handler, reload via signal disabled");
+            return;
+        }
     };
-    run_server(Arc::new(state), config.listen).await
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading config");
+            // Outcome is logged by the reloader; a rejected reload keeps the running config.
+            let _ = state.reloader.reload(&state).await;
+        }
+    });
 }
 
+#[cfg(not(unix))]
+fn spawn_reload_on_hangup<C: CacheBackend + 'static>(_state: Arc<AppState<C>>) {}
+
 async fn run_server<C: CacheBackend + 'static>(
     state: Arc<AppState<C>>,
     listen: String,
@@ -188,6 +307,10 @@ async fn run_server<C: CacheBackend + 'static>(
         .route("/stats", get(handler::stats))
         .route("/metrics", get(handler::metrics))
         .route("/health", get(handler::health))
+        .route("/health/stores", get(handler::store_health::<C>))
+        .route("/ready", get(handler::ready::<C>))
+        .route("/_reload", post(handler::reload_config::<C>))
+        .route("/_sign", post(handler::sign_urls::<C>))
         .merge(protected)
         .with_state(state)
         .layer(
@@ -211,6 +334,10 @@ async fn run_server<C: CacheBackend + 'static>(
                     Some("/metrics") => "http.r.metrics",
                     Some("/stats") => "http.r.stats",
                     Some("/health") => "http.r.health",
+                    Some("/health/stores") => "http.r.health_stores",
+                    Some("/ready") => "http.r.ready",
+                    Some("/_reload") => "http.r.reload",
+                    Some("/_sign") => "http.r.sign",
                     Some("/{bucket_id}/{*path}") => {
                         if request.method() == axum::http::Method::HEAD {
                             "http.r.head_object"
@@ -1,4 +1,4 @@
-use mixtrics::metrics::{BoxedCounterVec, BoxedHistogramVec, BoxedRegistry};
+use mixtrics::metrics::{BoxedCounterVec, BoxedGaugeVec, BoxedHistogramVec, BoxedRegistry};
 use mixtrics::registry::prometheus_0_13::PrometheusMetricsRegistry;
 use prometheus_0_13::proto::MetricFamily;
 use prometheus_0_13::{Encoder, Registry, TextEncoder};
@@ -50,6 +50,19 @@ pub struct Metrics {
     upstream_ok_total: BoxedCounterVec,
     upstream_err_total: BoxedCounterVec,
     upstream_latency_ms: BoxedHistogramVec,
+    remote_tier_total: BoxedCounterVec,
+    remote_tier_write_total: BoxedCounterVec,
+    mirror_member_total: BoxedCounterVec,
+    upstream_retry_total: BoxedCounterVec,
+    upstream_rejected_total: BoxedCounterVec,
+    breaker_transitions_total: BoxedCounterVec,
+    breaker_state: BoxedGaugeVec,
+    replication_total: BoxedCounterVec,
+    replication_lag_ms: BoxedHistogramVec,
+    throttle_bytes_total: BoxedCounterVec,
+    throttle_wait_ms_total: BoxedCounterVec,
+    throttle_waiting: BoxedGaugeVec,
+    bearer_total: BoxedCounterVec,
 }
 
 impl Metrics {
@@ -88,6 +101,66 @@ impl Metrics {
             "Total upstream errors".into(),
             &["method", "error_kind"],
         );
+        let remote_tier_total = registry_handle.register_counter_vec(
+            "cachegate_remote_tier_total".into(),
+            "Total remote cache tier lookups".into(),
+            &["result"],
+        );
+        let remote_tier_write_total = registry_handle.register_counter_vec(
+            "cachegate_remote_tier_write_total".into(),
+            "Total remote cache tier write-backs".into(),
+            &["status"],
+        );
+        let mirror_member_total = registry_handle.register_counter_vec(
+            "cachegate_mirror_member_total".into(),
+            "Total mirror member reads".into(),
+            &["store", "member", "result"],
+        );
+        let upstream_retry_total = registry_handle.register_counter_vec(
+            "cachegate_upstream_retry_total".into(),
+            "Total retryable upstream HTTP failures".into(),
+            &["store", "reason"],
+        );
+        let upstream_rejected_total = registry_handle.register_counter_vec(
+            "cachegate_upstream_rejected_total".into(),
+            "Total upstream requests rejected by concurrency limits or an open breaker".into(),
+            &["store", "reason"],
+        );
+        let breaker_transitions_total = registry_handle.register_counter_vec(
+            "cachegate_breaker_transitions_total".into(),
+            "Total circuit breaker state changes".into(),
+            &["store", "state"],
+        );
+        let breaker_state = registry_handle.register_gauge_vec(
+            "cachegate_breaker_state".into(),
+            "Circuit breaker state (0 closed, 1 half-open, 2 open)".into(),
+            &["store"],
+        );
+        let replication_total = registry_handle.register_counter_vec(
+            "cachegate_replication_total".into(),
+            "Total replica writes".into(),
+            &["store", "replica", "result"],
+        );
+        let throttle_bytes_total = registry_handle.register_counter_vec(
+            "cachegate_throttle_bytes_total".into(),
+            "Total upstream bytes passed through bandwidth limits".into(),
+            &["store", "direction"],
+        );
+        let throttle_wait_ms_total = registry_handle.register_counter_vec(
+            "cachegate_throttle_wait_ms_total".into(),
+            "Total time upstream transfers waited on bandwidth limits, in milliseconds".into(),
+            &["store", "direction"],
+        );
+        let throttle_waiting = registry_handle.register_gauge_vec(
+            "cachegate_throttle_waiting".into(),
+            "Upstream transfers currently paused by a bandwidth limit".into(),
+            &["store"],
+        );
+        let bearer_total = registry_handle.register_counter_vec(
+            "cachegate_bearer_total".into(),
+            "Bearer token authentications by token name".into(),
+            &["token", "result"],
+        );
 
         let buckets = vec![
             1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2000.0, 5000.0,
@@ -96,6 +169,12 @@ impl Metrics {
             "cachegate_upstream_latency_ms".into(),
             "Upstream request latency in milliseconds".into(),
             &["method"],
+            buckets.clone(),
+        );
+        let replication_lag_ms = registry_handle.register_histogram_vec_with_buckets(
+            "cachegate_replication_lag_ms".into(),
+            "Time replica writes completed after the primary write, in milliseconds".into(),
+            &["store"],
             buckets,
         );
 
@@ -109,6 +188,19 @@ impl Metrics {
             upstream_ok_total,
             upstream_err_total,
             upstream_latency_ms,
+            remote_tier_total,
+            remote_tier_write_total,
+            mirror_member_total,
+            upstream_retry_total,
+            upstream_rejected_total,
+            breaker_transitions_total,
+            breaker_state,
+            replication_total,
+            replication_lag_ms,
+            throttle_bytes_total,
+            throttle_wait_ms_total,
+            throttle_waiting,
+            bearer_total,
         }
     }
 
@@ -158,6 +250,88 @@ impl Metrics {
             .record(value_ms as f64);
     }
 
+    pub fn inc_remote_tier(&self, result: &str) {
+        self.remote_tier_total
+            .counter(&[owned_label(result)])
+            .increase(1);
+    }
+
+    pub fn inc_remote_tier_write(&self, status: &str) {
+        self.remote_tier_write_total
+            .counter(&[owned_label(status)])
+            .increase(1);
+    }
+
+    pub fn inc_mirror_member(&self, store: &str, member: &str, result: &str) {
+        self.mirror_member_total
+            .counter(&[owned_label(store), owned_label(member), owned_label(result)])
+            .increase(1);
+    }
+
+    pub fn inc_upstream_retry(&self, store: &str, reason: &str) {
+        self.upstream_retry_total
+            .counter(&[owned_label(store), owned_label(reason)])
+            .increase(1);
+    }
+
+    pub fn inc_upstream_rejected(&self, store: &str, reason: &str) {
+        self.upstream_rejected_total
+            .counter(&[owned_label(store), owned_label(reason)])
+            .increase(1);
+    }
+
+    pub fn set_breaker_state(&self, store: &str, state: &str, value: u64) {
+        self.breaker_transitions_total
+            .counter(&[owned_label(store), owned_label(state)])
+            .increase(1);
+        self.breaker_state
+            .gauge(&[owned_label(store)])
+            .absolute(value);
+    }
+
+    pub fn inc_replication(&self, store: &str, replica: &str, result: &str) {
+        self.replication_total
+            .counter(&[
+                owned_label(store),
+                owned_label(replica),
+                owned_label(result),
+            ])
+            .increase(1);
+    }
+
+    pub fn observe_replication_lag_ms(&self, store: &str, value_ms: u64) {
+        self.replication_lag_ms
+            .histogram(&[owned_label(store)])
+            .record(value_ms as f64);
+    }
+
+    pub fn observe_throttle(&self, store: &str, direction: &str, bytes: u64, waited_ms: u64) {
+        let labels = [owned_label(store), owned_label(direction)];
+        self.throttle_bytes_total.counter(&labels).increase(bytes);
+        self.throttle_wait_ms_total
+            .counter(&labels)
+            .increase(waited_ms);
+    }
+
+    pub fn inc_throttle_waiting(&self, store: &str) {
+        self.throttle_waiting
+            .gauge(&[owned_label(store)])
+            .increase(1);
+    }
+
+    pub fn dec_throttle_waiting(&self, store: &str) {
+        self.throttle_waiting
+            .gauge(&[owned_label(store)])
+            .decrease(1);
+    }
+
+    /// `result` is `ok` or `denied` (valid token, out of scope).
+    pub fn inc_bearer(&self, token: &str, result: &str) {
+        self.bearer_total
+            .counter(&[owned_label(token), owned_label(result)])
+            .increase(1);
+    }
+
     pub fn snapshot(&self) -> MetricsSnapshot {
         let metric_families = self.registry.gather();
         MetricsSnapshot {
@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreExt};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::config::ReadinessConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Ok,
+    Down,
+    /// The store cannot list and has no canary, so there is nothing cheap to ask it.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeReport {
+    pub status: ProbeStatus,
+    pub critical: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Cached reachability check for one store, behind `/ready`.
+#[derive(Debug)]
+pub struct ReadinessProbe {
+    canary: Option<Path>,
+    critical: bool,
+    interval: Duration,
+    timeout: Duration,
+    last: Mutex<Option<(Instant, ProbeReport)>>,
+}
+
+impl ReadinessProbe {
+    pub fn new(config: &ReadinessConfig) -> Self {
+        Self {
+            canary: config.canary.as_deref().map(Path::from),
+            critical: config.critical,
+            interval: config.interval,
+            timeout: config.timeout,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Returns the last result while it is fresh. Concurrent callers wait for one probe
+    /// instead of each reaching the store.
+    pub async fn check(&self, store: &dyn ObjectStore) -> ProbeReport {
+        let mut last = self.last.lock().await;
+        if let Some((at, report)) = last.as_ref()
+            && at.elapsed() < self.interval
+        {
+            return report.clone();
+        }
+
+        let report = self.probe(store).await;
+        *last = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    async fn probe(&self, store: &dyn ObjectStore) -> ProbeReport {
+        let start = Instant::now();
+        let request = async {
+            match &self.canary {
+                Some(canary) => store.head(canary).await.map(|_| ()),
+                // Only the first page is fetched; the stream is dropped after one entry.
+                None => store.list(None).next().await.transpose().map(|_| ()),
+            }
+        };
+        let (status, error) = match tokio::time::timeout(self.timeout, request).await {
+            Ok(Ok(())) => (ProbeStatus::Ok, None),
+            Ok(Err(
+                object_store::Error::NotImplemented { .. }
+                | object_store::Error::NotSupported { .. },
+            )) if self.canary.is_none() => (ProbeStatus::Unknown, None),
+            Ok(Err(err)) => (ProbeStatus::Down, Some(err.to_string())),
+            Err(_) => (ProbeStatus::Down, Some("probe timed out".to_string())),
+        };
+        ProbeReport {
+            status,
+            critical: self.critical,
+            latency_ms: start.elapsed().as_millis() as u64,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    fn probe(canary: Option<&str>, interval: Duration) -> ReadinessProbe {
+        ReadinessProbe::new(&ReadinessConfig {
+            canary: canary.map(str::to_string),
+            interval,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn missing_canary_is_down() {
+        let store = InMemory::new();
+        let probe = probe(Some("canary.txt"), Duration::ZERO);
+
+        let report = probe.check(&store).await;
+        assert_eq!(report.status, ProbeStatus::Down);
+        assert!(report.critical);
+
+        store
+            .put(&Path::from("canary.txt"), "ok".into())
+            .await
+            .unwrap();
+        assert_eq!(probe.check(&store).await.status, ProbeStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn reuses_result_within_interval() {
+        let store = InMemory::new();
+        let probe = probe(Some("canary.txt"), Duration::from_secs(60));
+
+        assert_eq!(probe.check(&store).await.status, ProbeStatus::Down);
+        store
+            .put(&Path::from("canary.txt"), "ok".into())
+            .await
+            .unwrap();
+        assert_eq!(probe.check(&store).await.status, ProbeStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn lists_root_without_canary() {
+        let probe = probe(None, Duration::ZERO);
+
+        assert_eq!(probe.check(&InMemory::new()).await.status, ProbeStatus::Ok);
+    }
+}
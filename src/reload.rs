@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::auth::AuthState;
+use crate::cache::CacheBackend;
+use crate::config::{Config, ConfigSource, StoreEntry};
+use crate::handler::AppState;
+use crate::store::build_stores;
+
+/// Re-reads the config and swaps stores and auth into the running state.
+///
+/// Listener, cache and sentry settings are only applied on restart.
+#[derive(Debug)]
+pub struct Reloader {
+    source: ConfigSource,
+    current: Mutex<Config>,
+}
+
+/// What a reload changed, by store id.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub auth_changed: bool,
+    /// Sections that differ from the running config but need a restart to take effect.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigDiff {
+    pub fn between(old: &Config, new: &Config) -> Self {
+        let mut added: Vec<String> = new
+            .stores
+            .keys()
+            .filter(|id| !old.stores.contains_key(*id))
+            .cloned()
+            .collect();
+        let mut removed: Vec<String> = old
+            .stores
+            .keys()
+            .filter(|id| !new.stores.contains_key(*id))
+            .cloned()
+            .collect();
+        let mut changed: Vec<String> = new
+            .stores
+            .iter()
+            .filter(|(id, entry)| old.stores.get(*id).is_some_and(|old| old != *entry))
+            .map(|(id, _)| id.clone())
+            .collect();
+        added.sort();
+        removed.sort();
+        changed.sort();
+
+        let mut restart_required = Vec::new();
+        if old.listen != new.listen {
+            restart_required.push("listen".to_string());
+        }
+        if old.cache != new.cache {
+            restart_required.push("cache".to_string());
+        }
+        if old.sentry != new.sentry {
+            restart_required.push("sentry".to_string());
+        }
+        // Use counters live in the replay store, which outlives auth reloads.
+        if old.auth.replay != new.auth.replay {
+            restart_required.push("auth.replay".to_string());
+        }
+        // The remote tier holds on to its store, so it is never swapped.
+        if let Some(remote) = old.cache.remote.as_ref() {
+            let id = &remote.store;
+            if added.contains(id) || removed.contains(id) || changed.contains(id) {
+                restart_required.push(format!("stores.{id}"));
+            }
+        }
+
+        Self {
+            added,
+            removed,
+            changed,
+            auth_changed: old.auth != new.auth,
+            restart_required,
+        }
+    }
+}
+
+impl Reloader {
+    pub fn new(source: ConfigSource, config: Config) -> Self {
+        Self {
+            source,
+            current: Mutex::new(config),
+        }
+    }
+
+    /// Reloads the config, leaving the running state untouched if anything fails to build.
+    pub async fn reload<C: CacheBackend>(&self, state: &AppState<C>) -> anyhow::Result<ConfigDiff> {
+        let result = self.try_reload(state).await;
+        match &result {
+            Ok(diff) => {
+                info!(
+                    added = ?diff.added,
+                    removed = ?diff.removed,
+                    changed = ?diff.changed,
+                    auth_changed = diff.auth_changed,
+                    "config reloaded"
+                );
+                if !diff.restart_required.is_empty() {
+                    warn!(sections = ?diff.restart_required, "config changes require a restart");
+                }
+            }
+            Err(err) => error!(error = %format!("{err:#}"), "config reload rejected"),
+        }
+        result
+    }
+
+    async fn try_reload<C: CacheBackend>(&self, state: &AppState<C>) -> anyhow::Result<ConfigDiff> {
+        // Held for the whole reload so concurrent triggers apply one after another.
+        let mut current = self.current.lock().await;
+        let config = self.source.load()?;
+        let diff = ConfigDiff::between(&current, &config);
+
+        let auth = AuthState::from_config(&config.auth).context("failed to initialize auth")?;
+
+        let remote_store = current.cache.remote.as_ref().map(|remote| &remote.store);
+        let rebuild: HashMap<String, StoreEntry> = config
+            .stores
+            .iter()
+            .filter(|(id, _)| diff.added.contains(id) || diff.changed.contains(id))
+            .filter(|(id, _)| Some(*id) != remote_store)
+            .map(|(id, entry)| (id.clone(), entry.clone()))
+            .collect();
+        let mut stores = build_stores(&rebuild, state.metrics.clone())
+            .await
+            .context("failed to build stores")?;
+
+        // Unchanged stores keep their connection pools and breaker state.
+        let running = state.stores.load();
+        for (id, store) in running.iter() {
+            if config.stores.contains_key(id) && !stores.contains_key(id) {
+                stores.insert(id.clone(), store.clone());
+            }
+        }
+
+        state.stores.store(Arc::new(stores));
+        state.auth.store(Arc::new(auth));
+        *current = config;
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const BASE: &str = r#"
+listen: "0.0.0.0:8080"
+auth:
+  public_key: "pub"
+  private_key: "priv"
+cache:
+  max_memory: 64MB
+stores:
+  a:
+    type: memory
+  b:
+    type: memory
+"#;
+
+    #[test]
+    fn reports_store_and_auth_changes() {
+        let old = config(BASE);
+        let new = config(
+            &BASE
+                .replace("  b:\n    type: memory\n", "  c:\n    type: memory\n")
+                .replace(
+                    "  a:\n    type: memory\n",
+                    "  a:\n    type: memory\n    read_only: true\n",
+                )
+                .replace("\"priv\"", "\"priv\"\n  bearer_token: \"t\""),
+        );
+
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(diff.added, vec!["c"]);
+        assert_eq!(diff.removed, vec!["b"]);
+        assert_eq!(diff.changed, vec!["a"]);
+        assert!(diff.auth_changed);
+        assert!(diff.restart_required.is_empty());
+    }
+
+    #[test]
+    fn flags_sections_that_need_a_restart() {
+        let old = config(BASE);
+        let new = config(&BASE.replace("64MB", "128MB").replace("8080", "9090"));
+
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(
+            diff,
+            ConfigDiff {
+                restart_required: vec!["listen".to_string(), "cache".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::config::{ReplayBackendKind, ReplayConfig};
+
+/// How often expired counters are swept, in seconds.
+const SWEEP_INTERVAL_SECS: i64 = 60;
+/// The file log is rewritten once it holds this many lines and twice the live lines.
+const COMPACT_MIN_LINES: usize = 10_000;
+
+/// Counts uses of limited-use presigned URLs, keyed by signature hash.
+#[async_trait]
+pub trait ReplayStore: Debug + Send + Sync {
+    /// Records one use and returns whether it was within `max_uses`. The counter may be
+    /// dropped once `expires_at` (unix seconds) has passed.
+    async fn consume(&self, key: &str, max_uses: u32, expires_at: i64) -> anyhow::Result<bool>;
+
+    /// Gives back a use taken by `consume`, for requests that did not succeed.
+    async fn refund(&self, key: &str) -> anyhow::Result<()>;
+}
+
+pub fn build_replay_store(config: &ReplayConfig) -> anyhow::Result<Arc<dyn ReplayStore>> {
+    match config.backend {
+        ReplayBackendKind::Memory => Ok(Arc::new(MemoryReplayStore::default())),
+        ReplayBackendKind::File => {
+            let path = config
+                .path
+                .as_deref()
+                .context("auth.replay.path is required for the file backend")?;
+            Ok(Arc::new(FileReplayStore::open(path)?))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    uses: HashMap<String, (u32, i64)>,
+    last_sweep: i64,
+}
+
+impl Counters {
+    fn consume(&mut self, key: &str, max_uses: u32, expires_at: i64, now: i64) -> bool {
+        if now - self.last_sweep >= SWEEP_INTERVAL_SECS {
+            self.uses.retain(|_, (_, expires_at)| *expires_at >= now);
+            self.last_sweep = now;
+        }
+        let (used, _) = self.uses.entry(key.to_string()).or_insert((0, expires_at));
+        if *used >= max_uses {
+            return false;
+        }
+        *used += 1;
+        true
+    }
+
+    fn record(&mut self, key: &str, expires_at: i64) {
+        self.uses
+            .entry(key.to_string())
+            .or_insert((0, expires_at))
+            .0 += 1;
+    }
+
+    fn refund(&mut self, key: &str) -> bool {
+        match self.uses.get_mut(key) {
+            Some((used, _)) if *used > 0 => {
+                *used -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Default backend. Counters reset on restart.
+#[derive(Debug, Default)]
+pub struct MemoryReplayStore {
+    counters: Mutex<Counters>,
+}
+
+#[async_trait]
+impl ReplayStore for MemoryReplayStore {
+    async fn consume(&self, key: &str, max_uses: u32, expires_at: i64) -> anyhow::Result<bool> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Ok(self
+            .counters
+            .lock()
+            .unwrap()
+            .consume(key, max_uses, expires_at, now))
+    }
+
+    async fn refund(&self, key: &str) -> anyhow::Result<()> {
+        self.counters.lock().unwrap().refund(key);
+        Ok(())
+    }
+}
+
+/// Keeps counters in memory and appends every use to a log, replayed on startup. Refunds are
+/// logged as `-<key>`.
+///
+/// The log is compacted on open, and again once it has grown well past the live counters,
+/// dropping entries whose URL has expired. File I/O runs on the blocking pool.
+#[derive(Debug)]
+pub struct FileReplayStore {
+    inner: Arc<Mutex<ReplayLog>>,
+}
+
+#[derive(Debug)]
+struct ReplayLog {
+    path: PathBuf,
+    counters: Counters,
+    file: File,
+    /// Lines in the log file, live or not.
+    lines: usize,
+    /// Line count that triggers the next compaction.
+    compact_at: usize,
+}
+
+impl ReplayLog {
+    fn append(&mut self, line: &str, now: i64) -> std::io::Result<()> {
+        writeln!(self.file, "{line}")?;
+        self.lines += 1;
+        if self.lines >= self.compact_at {
+            // The use is already on disk; a failed rewrite only leaves the log long.
+            if let Err(err) = self.compact(now) {
+                warn!(path = %self.path.display(), error = %err, "failed to compact replay log");
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log as one line per live use.
+    fn compact(&mut self, now: i64) -> anyhow::Result<()> {
+        let lines: Vec<String> = self
+            .counters
+            .uses
+            .iter()
+            .filter(|(_, (_, expires_at))| *expires_at >= now)
+            .flat_map(|(key, (used, expires_at))| {
+                std::iter::repeat_n(format!("{expires_at} {key}"), *used as usize)
+            })
+            .collect();
+        self.file = rewrite_log(&self.path, &lines)?;
+        self.lines = lines.len();
+        self.compact_at = compact_threshold(self.lines);
+        Ok(())
+    }
+}
+
+fn compact_threshold(live_lines: usize) -> usize {
+    COMPACT_MIN_LINES.max(live_lines * 2)
+}
+
+/// Atomically replaces the log with `lines` and reopens it for appending.
+fn rewrite_log(path: &Path, lines: &[String]) -> anyhow::Result<File> {
+    let compacted = PathBuf::from(format!("{}.tmp", path.display()));
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    std::fs::write(&compacted, contents)
+        .and_then(|_| std::fs::rename(&compacted, path))
+        .with_context(|| format!("failed to compact replay log {}", path.display()))?;
+
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open replay log {}", path.display()))
+}
+
+impl FileReplayStore {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut counters = Counters {
+            last_sweep: now,
+            ..Default::default()
+        };
+        let mut live = Vec::new();
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line
+                        .with_context(|| format!("failed to read replay log {}", path.display()))?;
+                    // A torn last line from a crash is skipped rather than failing startup.
+                    let Some((expires_at, key)) = line
+                        .split_once(' ')
+                        .and_then(|(expires_at, key)| Some((expires_at.parse::<i64>().ok()?, key)))
+                    else {
+                        continue;
+                    };
+                    if expires_at >= now {
+                        match key.strip_prefix('-') {
+                            Some(key) => {
+                                counters.refund(key);
+                            }
+                            None => counters.record(key, expires_at),
+                        }
+                        live.push(line);
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to open replay log {}", path.display()));
+            }
+        }
+
+        let file = rewrite_log(path, &live)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(ReplayLog {
+                path: path.to_path_buf(),
+                counters,
+                file,
+                lines: live.len(),
+                compact_at: compact_threshold(live.len()),
+            })),
+        })
+    }
+
+    /// Runs `f` against the log on the blocking pool, since it may write or rewrite the file.
+    async fn with_log<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut ReplayLog) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || f(&mut inner.lock().unwrap()))
+            .await
+            .context("replay log task failed")?
+    }
+}
+
+#[async_trait]
+impl ReplayStore for FileReplayStore {
+    async fn consume(&self, key: &str, max_uses: u32, expires_at: i64) -> anyhow::Result<bool> {
+        let key = key.to_string();
+        self.with_log(move |log| {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            if !log.counters.consume(&key, max_uses, expires_at, now) {
+                return Ok(false);
+            }
+            if let Err(err) = log.append(&format!("{expires_at} {key}"), now) {
+                // Not persisted, so do not count it either.
+                if let Some((used, _)) = log.counters.uses.get_mut(&key) {
+                    *used -= 1;
+                }
+                return Err(err).context("failed to append to replay log");
+            }
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn refund(&self, key: &str) -> anyhow::Result<()> {
+        let key = key.to_string();
+        self.with_log(move |log| {
+            let Some(&(_, expires_at)) = log.counters.uses.get(&key) else {
+                return Ok(());
+            };
+            if log.counters.refund(&key) {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                log.append(&format!("{expires_at} -{key}"), now)
+                    .context("failed to append to replay log")?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn future() -> i64 {
+        OffsetDateTime::now_utc().unix_timestamp() + 300
+    }
+
+    #[tokio::test]
+    async fn allows_up_to_max_uses() {
+        let store = MemoryReplayStore::default();
+        let exp = future();
+
+        assert!(store.consume("a", 2, exp).await.unwrap());
+        assert!(store.consume("a", 2, exp).await.unwrap());
+        assert!(!store.consume("a", 2, exp).await.unwrap());
+        assert!(store.consume("b", 1, exp).await.unwrap());
+    }
+
+    #[test]
+    fn sweeps_expired_counters() {
+        let mut counters = Counters::default();
+
+        assert!(counters.consume("a", 1, 100, 50));
+        assert!(!counters.consume("a", 1, 100, 60));
+        counters.consume("b", 1, 1000, 200);
+        assert!(!counters.uses.contains_key("a"));
+    }
+
+    #[tokio::test]
+    async fn file_backend_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("replay.log");
+        let exp = future();
+
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("a", 2, exp).await.unwrap());
+        assert!(store.consume("expired", 5, 1).await.unwrap());
+        drop(store);
+
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("a", 2, exp).await.unwrap());
+        assert!(!store.consume("a", 2, exp).await.unwrap());
+        drop(store);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(!log.contains("expired"));
+        assert_eq!(log.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn refunds_give_back_a_use() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("replay.log");
+        let exp = future();
+
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("a", 1, exp).await.unwrap());
+        store.refund("a").await.unwrap();
+        store.refund("unknown").await.unwrap();
+        assert!(store.consume("a", 1, exp).await.unwrap());
+        store.refund("a").await.unwrap();
+        drop(store);
+
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("a", 1, exp).await.unwrap());
+        assert!(!store.consume("a", 1, exp).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn file_backend_compacts_as_it_grows() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("replay.log");
+        let exp = future();
+
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("kept", 2, exp).await.unwrap());
+        for _ in 0..COMPACT_MIN_LINES {
+            assert!(store.consume("churn", 1, exp).await.unwrap());
+            store.refund("churn").await.unwrap();
+        }
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.lines().count() < COMPACT_MIN_LINES);
+        drop(store);
+        let store = FileReplayStore::open(&path).unwrap();
+        assert!(store.consume("kept", 2, exp).await.unwrap());
+        assert!(!store.consume("kept", 2, exp).await.unwrap());
+        assert!(store.consume("churn", 1, exp).await.unwrap());
+    }
+}
@@ -3,46 +3,129 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use object_store::ObjectStore;
-use object_store::azure::MicrosoftAzureBuilder;
+use object_store::azure::{AzureConfigKey, MicrosoftAzureBuilder};
 use tracing::info_span;
 
-#[derive(Debug, Clone)]
+use super::client::UpstreamClient;
+
+#[derive(Debug, Clone, Default)]
 struct AzureConnectionInfo {
-    account: String,
-    access_key: String,
+    account: Option<String>,
+    access_key: Option<String>,
+    sas_token: Option<String>,
     endpoint: Option<String>,
     allow_http: bool,
+    use_emulator: bool,
+}
+
+pub(crate) struct AzureOptions<'a> {
+    pub container: &'a str,
+    pub connection_string: Option<&'a str>,
+    pub account: Option<&'a str>,
+    pub sas_token: Option<&'a str>,
+    pub use_emulator: bool,
+    pub client_id: Option<&'a str>,
+    pub tenant_id: Option<&'a str>,
+    pub federated_token_file: Option<&'a str>,
+    pub msi_endpoint: Option<&'a str>,
+    pub upstream: UpstreamClient,
 }
 
 pub(crate) fn build_azure_store(
     store_id: &str,
-    container: &str,
-    connection_string: &str,
+    options: AzureOptions<'_>,
 ) -> anyhow::Result<Arc<dyn ObjectStore>> {
-    let info = parse_connection_string(connection_string)
-        .with_context(|| format!("failed to parse azure connection string for store {store_id}"))?;
+    let info = match options.connection_string {
+        Some(connection_string) => {
+            parse_connection_string(connection_string).with_context(|| {
+                format!("failed to parse azure connection string for store {store_id}")
+            })?
+        }
+        None => AzureConnectionInfo::default(),
+    };
+
+    if options.sas_token.is_some() && info.access_key.is_some() {
+        anyhow::bail!("store {store_id}: sas_token conflicts with AccountKey in connection_string");
+    }
+
+    let use_emulator = options.use_emulator || info.use_emulator;
+    let account = options.account.map(str::to_string).or(info.account);
+    if account.is_none() && !use_emulator {
+        anyhow::bail!(
+            "store {store_id}: azure account is required (set account or AccountName in connection_string)"
+        );
+    }
+    let sas_token = options
+        .sas_token
+        .map(str::to_string)
+        .or(info.sas_token)
+        .map(|value| value.trim_start_matches('?').to_string());
+
+    let auth = if info.access_key.is_some() {
+        "access_key"
+    } else if sas_token.is_some() {
+        "sas"
+    } else if use_emulator {
+        "emulator"
+    } else if options.client_id.is_some()
+        && options.tenant_id.is_some()
+        && options.federated_token_file.is_some()
+    {
+        "workload_identity"
+    } else {
+        "managed_identity"
+    };
 
     let span = info_span!(
         "azure_store_init",
         store_id = %store_id,
-        container = %container,
-        account = %info.account,
+        container = %options.container,
+        account = %account.as_deref().unwrap_or("devstoreaccount1"),
         endpoint = %info.endpoint.as_deref().unwrap_or("default"),
-        allow_http = info.allow_http
+        allow_http = info.allow_http,
+        auth = auth
     );
     let _enter = span.enter();
 
-    let mut builder = MicrosoftAzureBuilder::new()
-        .with_account(info.account)
-        .with_access_key(info.access_key)
-        .with_container_name(container);
+    let mut builder = MicrosoftAzureBuilder::new();
+    for (key, value) in options.upstream.settings() {
+        builder = builder.with_config(AzureConfigKey::Client(key), value);
+    }
+    builder = builder
+        .with_container_name(options.container)
+        .with_retry(options.upstream.retry())
+        .with_http_connector(options.upstream.connector());
 
+    if let Some(account) = account {
+        builder = builder.with_account(account);
+    }
+    if let Some(access_key) = info.access_key {
+        builder = builder.with_access_key(access_key);
+    }
+    if let Some(sas_token) = sas_token {
+        builder = builder.with_config(AzureConfigKey::SasKey, sas_token);
+    }
+    if use_emulator {
+        builder = builder.with_use_emulator(true);
+    }
     if let Some(endpoint) = info.endpoint {
         builder = builder.with_endpoint(endpoint);
     }
     if info.allow_http {
         builder = builder.with_allow_http(true);
     }
+    if let Some(client_id) = options.client_id {
+        builder = builder.with_client_id(client_id);
+    }
+    if let Some(tenant_id) = options.tenant_id {
+        builder = builder.with_tenant_id(tenant_id);
+    }
+    if let Some(path) = options.federated_token_file {
+        builder = builder.with_federated_token_file(path);
+    }
+    if let Some(endpoint) = options.msi_endpoint {
+        builder = builder.with_msi_endpoint(endpoint);
+    }
 
     Ok(Arc::new(builder.build()?))
 }
@@ -71,14 +154,27 @@ fn parse_connection_string(connection_string: &str) -> anyhow::Result<AzureConne
         values.insert(key.to_ascii_lowercase(), value);
     }
 
+    let use_emulator = values
+        .remove("usedevelopmentstorage")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"));
     let account = values
         .remove("accountname")
-        .filter(|value| !value.is_empty())
-        .context("missing AccountName")?;
+        .filter(|value| !value.is_empty());
     let access_key = values
         .remove("accountkey")
-        .filter(|value| !value.is_empty())
-        .context("missing AccountKey")?;
+        .filter(|value| !value.is_empty());
+    let sas_token = values
+        .remove("sharedaccesssignature")
+        .filter(|value| !value.is_empty());
+
+    if !use_emulator {
+        if account.is_none() && sas_token.is_none() {
+            anyhow::bail!("missing AccountName");
+        }
+        if access_key.is_none() && sas_token.is_none() {
+            anyhow::bail!("missing AccountKey or SharedAccessSignature");
+        }
+    }
 
     let endpoint = values
         .remove("blobendpoint")
@@ -89,15 +185,18 @@ fn parse_connection_string(connection_string: &str) -> anyhow::Result<AzureConne
         .map(|value| value.to_ascii_lowercase());
     let endpoint_suffix = values.remove("endpointsuffix");
 
-    let endpoint = match (endpoint, endpoint_suffix) {
-        (Some(endpoint), _) => Some(endpoint),
-        (None, Some(suffix)) => {
+    let endpoint = match (endpoint, endpoint_suffix, account.as_deref()) {
+        (Some(endpoint), _, _) => Some(endpoint),
+        (None, Some(suffix), Some(account)) => {
             let scheme = protocol.as_deref().unwrap_or("https");
             Some(format!("{scheme}://{account}.blob.{suffix}"))
         }
-        (None, None) => None,
+        _ => None,
     };
 
+    // SAS connection strings usually carry only BlobEndpoint, which names the account.
+    let account = account.or_else(|| endpoint.as_deref().and_then(account_from_endpoint));
+
     let allow_http = endpoint
         .as_deref()
         .is_some_and(|value| value.to_ascii_lowercase().starts_with("http://"))
@@ -106,22 +205,38 @@ fn parse_connection_string(connection_string: &str) -> anyhow::Result<AzureConne
     Ok(AzureConnectionInfo {
         account,
         access_key,
+        sas_token,
         endpoint,
         allow_http,
+        use_emulator,
     })
 }
 
+/// `https://<account>.blob.<suffix>/...`, or `http://host:port/<account>` for path-style
+/// endpoints such as Azurite.
+fn account_from_endpoint(endpoint: &str) -> Option<String> {
+    let rest = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let account = match host.split_once(".blob.") {
+        Some((account, _)) => account,
+        None => path.split('/').next().unwrap_or_default(),
+    };
+    (!account.is_empty()).then(|| account.to_string())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_connection_string;
+    use super::{AzureOptions, UpstreamClient, build_azure_store, parse_connection_string};
 
     #[test]
     fn parses_account_and_key() {
         let connection_string = "AccountName=cachegate;AccountKey=secret";
         let info = parse_connection_string(connection_string).unwrap();
 
-        assert_eq!(info.account, "cachegate");
-        assert_eq!(info.access_key, "secret");
+        assert_eq!(info.account.as_deref(), Some("cachegate"));
+        assert_eq!(info.access_key.as_deref(), Some("secret"));
         assert_eq!(info.endpoint, None);
         assert!(!info.allow_http);
     }
@@ -131,7 +246,7 @@ mod tests {
         let connection_string = "AccountName=cachegate;AccountKey=abc==";
         let info = parse_connection_string(connection_string).unwrap();
 
-        assert_eq!(info.access_key, "abc==");
+        assert_eq!(info.access_key.as_deref(), Some("abc=="));
     }
 
     #[test]
@@ -166,4 +281,116 @@ mod tests {
         );
         assert!(info.allow_http);
     }
+
+    #[test]
+    fn parses_shared_access_signature() {
+        let connection_string = concat!(
+            "AccountName=cachegate;",
+            "SharedAccessSignature=sv=2022-11-02&sr=c&sig=abc%3D"
+        );
+        let info = parse_connection_string(connection_string).unwrap();
+
+        assert_eq!(info.access_key, None);
+        assert_eq!(
+            info.sas_token.as_deref(),
+            Some("sv=2022-11-02&sr=c&sig=abc%3D")
+        );
+    }
+
+    #[test]
+    fn takes_account_from_blob_endpoint() {
+        let info = parse_connection_string(concat!(
+            "BlobEndpoint=https://cachegate.blob.core.windows.net/;",
+            "SharedAccessSignature=sv=2022-11-02&sr=c&sig=abc%3D"
+        ))
+        .unwrap();
+        assert_eq!(info.account.as_deref(), Some("cachegate"));
+
+        let info = parse_connection_string(concat!(
+            "BlobEndpoint=http://127.0.0.1:10000/devstoreaccount1;",
+            "SharedAccessSignature=sv=2022-11-02&sig=abc"
+        ))
+        .unwrap();
+        assert_eq!(info.account.as_deref(), Some("devstoreaccount1"));
+    }
+
+    #[test]
+    fn rejects_missing_credentials() {
+        let err = parse_connection_string("AccountName=cachegate").unwrap_err();
+
+        assert!(err.to_string().contains("SharedAccessSignature"));
+    }
+
+    #[test]
+    fn parses_development_storage_shortcut() {
+        let info = parse_connection_string("UseDevelopmentStorage=true").unwrap();
+
+        assert!(info.use_emulator);
+        assert_eq!(info.account, None);
+    }
+
+    fn options(container: &str) -> AzureOptions<'_> {
+        AzureOptions {
+            container,
+            connection_string: None,
+            account: None,
+            sas_token: None,
+            use_emulator: false,
+            client_id: None,
+            tenant_id: None,
+            federated_token_file: None,
+            msi_endpoint: None,
+            upstream: UpstreamClient::default(),
+        }
+    }
+
+    #[test]
+    fn builds_emulator_store_without_credentials() {
+        let options = AzureOptions {
+            use_emulator: true,
+            ..options("cachegate")
+        };
+
+        assert!(build_azure_store("azurite", options).is_ok());
+    }
+
+    #[test]
+    fn builds_store_from_sas_token() {
+        let options = AzureOptions {
+            account: Some("cachegate"),
+            sas_token: Some("?sv=2022-11-02&sr=c&sig=abc%3D"),
+            ..options("cachegate")
+        };
+
+        assert!(build_azure_store("sas", options).is_ok());
+    }
+
+    #[test]
+    fn builds_store_from_sas_connection_string() {
+        let options = AzureOptions {
+            connection_string: Some(concat!(
+                "BlobEndpoint=https://cachegate.blob.core.windows.net/;",
+                "SharedAccessSignature=sv=2022-11-02&sr=c&sig=abc%3D"
+            )),
+            ..options("cachegate")
+        };
+
+        assert!(build_azure_store("sas", options).is_ok());
+    }
+
+    #[test]
+    fn rejects_sas_token_alongside_account_key() {
+        let options = AzureOptions {
+            connection_string: Some("AccountName=cachegate;AccountKey=c2VjcmV0"),
+            sas_token: Some("sv=2022-11-02&sig=abc"),
+            ..options("cachegate")
+        };
+
+        assert!(build_azure_store("conflict", options).is_err());
+    }
+
+    #[test]
+    fn requires_account_outside_emulator() {
+        assert!(build_azure_store("missing", options("cachegate")).is_err());
+    }
 }
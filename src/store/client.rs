@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher, RandomState};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use object_store::client::{
+    ClientConfigKey, ClientOptions, HttpClient, HttpConnector, HttpError, HttpErrorKind,
+    HttpRequest, HttpResponse, HttpService, ReqwestConnector,
+};
+use object_store::{BackoffConfig, RetryConfig};
+
+use crate::config::{UpstreamClientConfig, UpstreamRetryConfig};
+use crate::metrics::Metrics;
+
+/// Client and retry settings shared by the network-backed store builders.
+#[derive(Debug, Clone)]
+pub(crate) struct UpstreamClient {
+    settings: Vec<(ClientConfigKey, String)>,
+    retry: RetryConfig,
+    connector: RetryCountingConnector,
+}
+
+impl UpstreamClient {
+    pub fn new(
+        store_id: &str,
+        client: &UpstreamClientConfig,
+        retry: &UpstreamRetryConfig,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let retry = retry_config(retry);
+        Self {
+            settings: client_settings(client),
+            connector: RetryCountingConnector {
+                store_id: store_id.to_string(),
+                metrics,
+                retry_window: retry.backoff.max_backoff + RETRY_WINDOW_SLACK,
+            },
+            retry,
+        }
+    }
+
+    /// Client options as config keys, so they merge with anything a builder read from the
+    /// environment instead of replacing it.
+    pub fn settings(&self) -> impl Iterator<Item = (ClientConfigKey, &str)> {
+        self.settings
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+    }
+
+    pub fn retry(&self) -> RetryConfig {
+        self.retry.clone()
+    }
+
+    pub fn connector(&self) -> RetryCountingConnector {
+        self.connector.clone()
+    }
+
+    /// Fresh [`ClientOptions`] for builders that take them whole.
+    pub fn options(&self) -> ClientOptions {
+        self.settings()
+            .fold(ClientOptions::new(), |options, (key, value)| {
+                options.with_config(key, value)
+            })
+    }
+}
+
+#[cfg(test)]
+impl Default for UpstreamClient {
+    fn default() -> Self {
+        Self::new(
+            "test",
+            &UpstreamClientConfig::default(),
+            &UpstreamRetryConfig::default(),
+            Arc::new(Metrics::new()),
+        )
+    }
+}
+
+fn client_settings(config: &UpstreamClientConfig) -> Vec<(ClientConfigKey, String)> {
+    let durations = [
+        (ClientConfigKey::ConnectTimeout, config.connect_timeout),
+        (ClientConfigKey::Timeout, config.timeout),
+        (ClientConfigKey::PoolIdleTimeout, config.pool_idle_timeout),
+    ];
+    let mut settings: Vec<_> = durations
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, humantime::format_duration(value?).to_string())))
+        .collect();
+
+    if let Some(max) = config.pool_max_idle_per_host {
+        settings.push((ClientConfigKey::PoolMaxIdlePerHost, max.to_string()));
+    }
+    if let Some(http2_only) = config.http2_only {
+        settings.push((ClientConfigKey::Http2Only, http2_only.to_string()));
+    }
+    if let Some(proxy_url) = &config.proxy_url {
+        settings.push((ClientConfigKey::ProxyUrl, proxy_url.clone()));
+    }
+    if let Some(proxy_excludes) = &config.proxy_excludes {
+        settings.push((ClientConfigKey::ProxyExcludes, proxy_excludes.clone()));
+    }
+    settings
+}
+
+fn retry_config(config: &UpstreamRetryConfig) -> RetryConfig {
+    let defaults = RetryConfig::default();
+    RetryConfig {
+        backoff: BackoffConfig {
+            init_backoff: config.init_backoff.unwrap_or(defaults.backoff.init_backoff),
+            max_backoff: config.max_backoff.unwrap_or(defaults.backoff.max_backoff),
+            base: config.backoff_base.unwrap_or(defaults.backoff.base),
+        },
+        max_retries: config.max_retries.unwrap_or(defaults.max_retries),
+        retry_timeout: config.retry_timeout.unwrap_or(defaults.retry_timeout),
+    }
+}
+
+/// Added to the longest backoff when matching a retry to the attempt that failed.
+const RETRY_WINDOW_SLACK: Duration = Duration::from_secs(1);
+
+/// Wraps the reqwest connector to count upstream attempts that object_store retried.
+#[derive(Debug, Clone)]
+pub(crate) struct RetryCountingConnector {
+    store_id: String,
+    metrics: Arc<Metrics>,
+    retry_window: Duration,
+}
+
+impl HttpConnector for RetryCountingConnector {
+    fn connect(&self, options: &ClientOptions) -> object_store::Result<HttpClient> {
+        let inner = ReqwestConnector::default().connect(options)?;
+        Ok(HttpClient::new(RetryCountingService {
+            inner,
+            store_id: self.store_id.clone(),
+            metrics: self.metrics.clone(),
+            retry_window: self.retry_window,
+            failed: Mutex::new(HashMap::new()),
+            hasher: RandomState::new(),
+        }))
+    }
+}
+
+/// object_store resends the same signed request on retry, so a retry is an attempt matching one
+/// that failed within the longest backoff. Failures that were not retried age out uncounted.
+#[derive(Debug)]
+struct RetryCountingService {
+    inner: HttpClient,
+    store_id: String,
+    metrics: Arc<Metrics>,
+    retry_window: Duration,
+    failed: Mutex<HashMap<u64, (Instant, &'static str)>>,
+    hasher: RandomState,
+}
+
+impl RetryCountingService {
+    fn fingerprint(&self, req: &HttpRequest) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        req.method().hash(&mut hasher);
+        req.uri().hash(&mut hasher);
+        for (name, value) in req.headers() {
+            name.hash(&mut hasher);
+            value.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[async_trait]
+impl HttpService for RetryCountingService {
+    async fn call(&self, req: HttpRequest) -> Result<HttpResponse, HttpError> {
+        let fingerprint = self.fingerprint(&req);
+        let retried = self.failed.lock().unwrap().remove(&fingerprint);
+        if let Some((failed_at, reason)) = retried
+            && failed_at.elapsed() <= self.retry_window
+        {
+            self.metrics.inc_upstream_retry(&self.store_id, reason);
+        }
+
+        let result = self.inner.execute(req).await;
+        if let Some(reason) = retry_reason(&result) {
+            let now = Instant::now();
+            let mut failed = self.failed.lock().unwrap();
+            failed.retain(|_, (failed_at, _)| now.duration_since(*failed_at) <= self.retry_window);
+            failed.insert(fingerprint, (now, reason));
+        }
+        result
+    }
+}
+
+/// Mirrors the outcomes object_store's retry loop backs off on.
+pub(super) fn retry_reason(result: &Result<HttpResponse, HttpError>) -> Option<&'static str> {
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            (status.is_server_error()
+                || status == StatusCode::TOO_MANY_REQUESTS
+                || status == StatusCode::REQUEST_TIMEOUT)
+                .then_some("status")
+        }
+        Err(err) => matches!(
+            err.kind(),
+            HttpErrorKind::Connect
+                | HttpErrorKind::Request
+                | HttpErrorKind::Timeout
+                | HttpErrorKind::Interrupted
+        )
+        .then_some("transport"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn maps_client_settings_to_config_keys() {
+        let config = UpstreamClientConfig {
+            connect_timeout: Some(Duration::from_secs(2)),
+            timeout: Some(Duration::from_millis(1500)),
+            pool_max_idle_per_host: Some(8),
+            http2_only: Some(true),
+            ..Default::default()
+        };
+        let client = UpstreamClient::new(
+            "s3",
+            &config,
+            &UpstreamRetryConfig::default(),
+            Arc::new(Metrics::new()),
+        );
+
+        let options = client.options();
+        assert_eq!(
+            options
+                .get_config_value(&ClientConfigKey::ConnectTimeout)
+                .as_deref(),
+            Some("2s")
+        );
+        assert_eq!(
+            options
+                .get_config_value(&ClientConfigKey::Timeout)
+                .as_deref(),
+            Some("1s 500ms")
+        );
+        assert_eq!(
+            options
+                .get_config_value(&ClientConfigKey::PoolMaxIdlePerHost)
+                .as_deref(),
+            Some("8")
+        );
+        assert_eq!(
+            options
+                .get_config_value(&ClientConfigKey::Http2Only)
+                .as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn retry_config_keeps_defaults_for_unset_fields() {
+        let retry = retry_config(&UpstreamRetryConfig {
+            max_retries: Some(2),
+            init_backoff: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let defaults = RetryConfig::default();
+
+        assert_eq!(retry.max_retries, 2);
+        assert_eq!(retry.backoff.init_backoff, Duration::from_millis(50));
+        assert_eq!(retry.backoff.max_backoff, defaults.backoff.max_backoff);
+        assert_eq!(retry.retry_timeout, defaults.retry_timeout);
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use object_store::ObjectStore;
+use object_store::gcp::{GoogleCloudStorageBuilder, GoogleConfigKey};
+use tracing::info_span;
+
+use super::client::UpstreamClient;
+
+pub(crate) struct GcsOptions<'a> {
+    pub bucket: &'a str,
+    pub service_account_key: Option<&'a str>,
+    pub service_account_path: Option<&'a str>,
+    pub endpoint: Option<&'a str>,
+    pub skip_signature: bool,
+    pub upstream: UpstreamClient,
+}
+
+pub(crate) fn build_gcs_store(
+    store_id: &str,
+    options: GcsOptions<'_>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    if options.service_account_key.is_some() && options.service_account_path.is_some() {
+        anyhow::bail!(
+            "store {store_id}: service_account_key and service_account_path are mutually exclusive"
+        );
+    }
+
+    let span = info_span!(
+        "gcs_store_init",
+        store_id = %store_id,
+        bucket = %options.bucket,
+        endpoint = %options.endpoint.unwrap_or("default"),
+        skip_signature = options.skip_signature
+    );
+    let _enter = span.enter();
+
+    let mut builder = GoogleCloudStorageBuilder::new();
+    for (key, value) in options.upstream.settings() {
+        builder = builder.with_config(GoogleConfigKey::Client(key), value);
+    }
+    builder = builder
+        .with_bucket_name(options.bucket)
+        .with_retry(options.upstream.retry())
+        .with_http_connector(options.upstream.connector());
+
+    if let Some(key) = options.service_account_key {
+        builder = builder.with_service_account_key(key);
+    }
+    if let Some(path) = options.service_account_path {
+        builder = builder.with_service_account_path(path);
+    }
+    if let Some(endpoint) = options.endpoint {
+        builder = builder.with_base_url(endpoint);
+    }
+    if options.skip_signature {
+        builder = builder.with_skip_signature(true);
+    }
+
+    Ok(Arc::new(builder.build()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GcsOptions, UpstreamClient, build_gcs_store};
+
+    #[test]
+    fn rejects_both_service_account_sources() {
+        let options = GcsOptions {
+            bucket: "cachegate",
+            service_account_key: Some("{}"),
+            service_account_path: Some("/tmp/sa.json"),
+            endpoint: None,
+            skip_signature: false,
+            upstream: UpstreamClient::default(),
+        };
+        assert!(build_gcs_store("gcs", options).is_err());
+    }
+
+    #[test]
+    fn builds_emulator_store_without_credentials() {
+        let options = GcsOptions {
+            bucket: "cachegate",
+            service_account_key: None,
+            service_account_path: None,
+            endpoint: Some("http://127.0.0.1:4443"),
+            skip_signature: true,
+            upstream: UpstreamClient::default(),
+        };
+        assert!(build_gcs_store("gcs", options).is_ok());
+    }
+}
@@ -0,0 +1,431 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::config::{BreakerConfig, ConcurrencyConfig};
+use crate::metrics::{Metrics, UpstreamErrorKind};
+
+/// Retry-After hint while a half-open probe is still in flight.
+const PROBE_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Why a request was turned away before reaching the upstream.
+#[derive(Debug, Clone, Copy)]
+pub enum Rejection {
+    /// No concurrency slot freed up within the queue timeout.
+    Saturated { retry_after: Duration },
+    /// The circuit breaker is open.
+    Open { retry_after: Duration },
+}
+
+impl Rejection {
+    pub fn retry_after(self) -> Duration {
+        match self {
+            Self::Saturated { retry_after } | Self::Open { retry_after } => retry_after,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Saturated { .. } => "saturated",
+            Self::Open { .. } => "open",
+        }
+    }
+}
+
+/// Per-store backpressure: a concurrency cap plus an error-rate circuit breaker.
+#[derive(Debug)]
+pub struct UpstreamGuard {
+    store_id: String,
+    limit: Option<(Semaphore, usize, Duration)>,
+    breaker: Option<CircuitBreaker>,
+    metrics: Arc<Metrics>,
+}
+
+impl UpstreamGuard {
+    pub fn new(
+        store_id: &str,
+        concurrency: Option<&ConcurrencyConfig>,
+        breaker: Option<&BreakerConfig>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            store_id: store_id.to_string(),
+            limit: concurrency.map(|config| {
+                (
+                    Semaphore::new(config.max_inflight.get()),
+                    config.max_inflight.get(),
+                    config.queue_timeout,
+                )
+            }),
+            breaker: breaker.map(|config| CircuitBreaker::new(config.clone())),
+            metrics,
+        }
+    }
+
+    /// Waits for a concurrency slot and checks the breaker. Hold the permit for the whole
+    /// upstream exchange and report the outcome through it.
+    pub async fn acquire(&self) -> Result<UpstreamPermit<'_>, Rejection> {
+        let result = self.admit().await;
+        if let Err(rejection) = result {
+            self.metrics
+                .inc_upstream_rejected(&self.store_id, rejection.as_str());
+        }
+        result
+    }
+
+    async fn admit(&self) -> Result<UpstreamPermit<'_>, Rejection> {
+        let slot = match &self.limit {
+            Some((semaphore, _, queue_timeout)) => {
+                match tokio::time::timeout(*queue_timeout, semaphore.acquire()).await {
+                    Ok(Ok(slot)) => Some(slot),
+                    _ => {
+                        return Err(Rejection::Saturated {
+                            retry_after: (*queue_timeout).max(PROBE_RETRY_AFTER),
+                        });
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let probe = match &self.breaker {
+            Some(breaker) => breaker.admit(Instant::now(), self)?,
+            None => false,
+        };
+
+        Ok(UpstreamPermit {
+            guard: self,
+            _slot: slot,
+            probe,
+            recorded: false,
+        })
+    }
+
+    pub fn status(&self) -> GuardStatus {
+        GuardStatus {
+            breaker: self
+                .breaker
+                .as_ref()
+                .map(|breaker| breaker.phase_name(Instant::now(), self)),
+            inflight: self
+                .limit
+                .as_ref()
+                .map(|(semaphore, max, _)| max - semaphore.available_permits()),
+            max_inflight: self.limit.as_ref().map(|(_, max, _)| *max),
+        }
+    }
+
+    /// Brings the breaker state gauge up to date before metrics are rendered.
+    pub fn refresh_breaker(&self) {
+        if let Some(breaker) = &self.breaker {
+            breaker.phase_name(Instant::now(), self);
+        }
+    }
+
+    fn transition(&self, phase: Phase) {
+        let (name, value) = match phase {
+            Phase::Closed => ("closed", 0),
+            Phase::HalfOpen => ("half_open", 1),
+            Phase::Open { .. } => ("open", 2),
+        };
+        self.metrics.set_breaker_state(&self.store_id, name, value);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuardStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breaker: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inflight: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_inflight: Option<usize>,
+}
+
+/// Admission to the upstream. Dropping it without recording counts as neither outcome.
+pub struct UpstreamPermit<'a> {
+    guard: &'a UpstreamGuard,
+    _slot: Option<SemaphorePermit<'a>>,
+    probe: bool,
+    recorded: bool,
+}
+
+impl UpstreamPermit<'_> {
+    pub fn record_ok(mut self) {
+        self.record(false);
+    }
+
+    /// Only errors that point at a degraded upstream count towards the breaker.
+    pub fn record_err(mut self, error: &object_store::Error) {
+        let failed = matches!(
+            UpstreamErrorKind::from_store_error(error),
+            UpstreamErrorKind::Other
+        );
+        self.record(failed);
+    }
+
+    fn record(&mut self, failed: bool) {
+        self.recorded = true;
+        if let Some(breaker) = &self.guard.breaker {
+            breaker.record(Instant::now(), self.probe, failed, self.guard);
+        }
+    }
+}
+
+impl Drop for UpstreamPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe
+            && !self.recorded
+            && let Some(breaker) = &self.guard.breaker
+        {
+            breaker.release_probe();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Closed,
+    Open { until: Instant },
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    phase: Phase,
+    probing: bool,
+    window_start: Instant,
+    requests: u32,
+    failures: u32,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BreakerState {
+                phase: Phase::Closed,
+                probing: false,
+                window_start: Instant::now(),
+                requests: 0,
+                failures: 0,
+            }),
+        }
+    }
+
+    /// Returns whether the admitted request is the half-open probe.
+    fn admit(&self, now: Instant, guard: &UpstreamGuard) -> Result<bool, Rejection> {
+        let mut state = self.state.lock().unwrap();
+        match state.phase {
+            Phase::Closed => Ok(false),
+            Phase::Open { until } if now < until => Err(Rejection::Open {
+                retry_after: until - now,
+            }),
+            Phase::Open { .. } => {
+                state.phase = Phase::HalfOpen;
+                state.probing = true;
+                guard.transition(Phase::HalfOpen);
+                Ok(true)
+            }
+            Phase::HalfOpen if state.probing => Err(Rejection::Open {
+                retry_after: PROBE_RETRY_AFTER,
+            }),
+            Phase::HalfOpen => {
+                state.probing = true;
+                Ok(true)
+            }
+        }
+    }
+
+    fn record(&self, now: Instant, probe: bool, failed: bool, guard: &UpstreamGuard) {
+        let mut state = self.state.lock().unwrap();
+        if probe {
+            state.probing = false;
+            let phase = if failed {
+                Phase::Open {
+                    until: now + self.config.open_for,
+                }
+            } else {
+                state.window_start = now;
+                state.requests = 0;
+                state.failures = 0;
+                Phase::Closed
+            };
+            state.phase = phase;
+            guard.transition(phase);
+            return;
+        }
+        if state.phase != Phase::Closed {
+            return;
+        }
+
+        if now.duration_since(state.window_start) >= self.config.window {
+            state.window_start = now;
+            state.requests = 0;
+            state.failures = 0;
+        }
+        state.requests += 1;
+        if failed {
+            state.failures += 1;
+        }
+
+        let rate = f64::from(state.failures) / f64::from(state.requests);
+        if state.requests >= self.config.min_requests && rate >= self.config.failure_rate {
+            let phase = Phase::Open {
+                until: now + self.config.open_for,
+            };
+            state.phase = phase;
+            guard.transition(phase);
+        }
+    }
+
+    fn release_probe(&self) {
+        self.state.lock().unwrap().probing = false;
+    }
+
+    /// Moves an expired open breaker to half-open, so readers never see a stale `open`.
+    fn phase_name(&self, now: Instant, guard: &UpstreamGuard) -> &'static str {
+        let mut state = self.state.lock().unwrap();
+        if let Phase::Open { until } = state.phase
+            && now >= until
+        {
+            state.phase = Phase::HalfOpen;
+            guard.transition(Phase::HalfOpen);
+        }
+        match state.phase {
+            Phase::Closed => "closed",
+            Phase::Open { .. } => "open",
+            Phase::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    fn upstream_error() -> object_store::Error {
+        object_store::Error::Generic {
+            store: "test",
+            source: "connection reset".into(),
+        }
+    }
+
+    fn guard(open_for: Duration) -> UpstreamGuard {
+        UpstreamGuard::new(
+            "s3",
+            None,
+            Some(&BreakerConfig {
+                failure_rate: 0.5,
+                min_requests: 4,
+                window: Duration::from_secs(60),
+                open_for,
+            }),
+            Arc::new(Metrics::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn opens_after_failure_rate_and_recovers_through_probe() {
+        let guard = guard(Duration::from_millis(20));
+
+        for _ in 0..2 {
+            guard.acquire().await.unwrap().record_ok();
+        }
+        for _ in 0..2 {
+            guard.acquire().await.unwrap().record_err(&upstream_error());
+        }
+        assert!(matches!(guard.acquire().await, Err(Rejection::Open { .. })));
+        assert_eq!(guard.status().breaker, Some("open"));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let probe = guard.acquire().await.unwrap();
+        assert!(matches!(guard.acquire().await, Err(Rejection::Open { .. })));
+        probe.record_ok();
+
+        assert_eq!(guard.status().breaker, Some("closed"));
+        assert!(guard.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reports_half_open_once_open_for_expires() {
+        let guard = guard(Duration::from_millis(20));
+        for _ in 0..4 {
+            guard.acquire().await.unwrap().record_err(&upstream_error());
+        }
+        assert!(
+            guard
+                .metrics
+                .render_prometheus()
+                .contains("cachegate_breaker_state{store=\"s3\"} 2")
+        );
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        guard.refresh_breaker();
+        assert!(
+            guard
+                .metrics
+                .render_prometheus()
+                .contains("cachegate_breaker_state{store=\"s3\"} 1")
+        );
+        assert_eq!(guard.status().breaker, Some("half_open"));
+
+        // The first request after the deadline is still the probe.
+        let probe = guard.acquire().await.unwrap();
+        assert!(matches!(guard.acquire().await, Err(Rejection::Open { .. })));
+        probe.record_ok();
+        assert_eq!(guard.status().breaker, Some("closed"));
+    }
+
+    #[tokio::test]
+    async fn not_found_does_not_trip_breaker() {
+        let guard = guard(Duration::from_secs(60));
+        let not_found = object_store::Error::NotFound {
+            path: "a.txt".to_string(),
+            source: "missing".into(),
+        };
+
+        for _ in 0..8 {
+            guard.acquire().await.unwrap().record_err(&not_found);
+        }
+        assert!(guard.acquire().await.is_ok());
+    }
+
+    #[test]
+    fn rejects_zero_max_inflight() {
+        assert!(serde_yaml::from_str::<ConcurrencyConfig>("max_inflight: 0").is_err());
+        assert!(serde_yaml::from_str::<ConcurrencyConfig>("max_inflight: 4").is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_when_saturated() {
+        let guard = UpstreamGuard::new(
+            "s3",
+            Some(&ConcurrencyConfig {
+                max_inflight: NonZeroUsize::MIN,
+                queue_timeout: Duration::from_millis(10),
+            }),
+            None,
+            Arc::new(Metrics::new()),
+        );
+
+        let held = guard.acquire().await.unwrap();
+        assert_eq!(guard.status().inflight, Some(1));
+        assert!(matches!(
+            guard.acquire().await,
+            Err(Rejection::Saturated { .. })
+        ));
+        drop(held);
+        assert!(guard.acquire().await.is_ok());
+    }
+}
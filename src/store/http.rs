@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use object_store::http::HttpBuilder;
+use object_store::path::Path;
+use object_store::{
+    CopyOptions, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOptions, PutOptions, PutPayload, PutResult, RenameOptions,
+};
+use tracing::info_span;
+
+use super::client::UpstreamClient;
+
+pub(crate) fn build_http_store(
+    store_id: &str,
+    url: &str,
+    headers: &HashMap<String, String>,
+    allow_http: bool,
+    upstream: UpstreamClient,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    let span = info_span!(
+        "http_store_init",
+        store_id = %store_id,
+        url = %url,
+        headers = headers.len(),
+        allow_http
+    );
+    let _enter = span.enter();
+
+    let mut options = upstream.options().with_allow_http(allow_http);
+    if !headers.is_empty() {
+        options = options.with_default_headers(
+            parse_headers(headers)
+                .with_context(|| format!("invalid static headers for store {store_id}"))?,
+        );
+    }
+
+    let store = HttpBuilder::new()
+        .with_url(url)
+        .with_client_options(options)
+        .with_retry(upstream.retry())
+        .with_http_connector(upstream.connector())
+        .build()?;
+
+    Ok(Arc::new(ReadOnlyStore {
+        inner: Arc::new(store),
+    }))
+}
+
+fn parse_headers(headers: &HashMap<String, String>) -> anyhow::Result<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("invalid header name {name}"))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("invalid value for header {name}"))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// Rejects every mutating operation, so plain HTTP origins can only be read through.
+#[derive(Debug)]
+struct ReadOnlyStore {
+    inner: Arc<dyn ObjectStore>,
+}
+
+impl fmt::Display for ReadOnlyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReadOnly({})", self.inner)
+    }
+}
+
+fn read_only(operation: &str) -> object_store::Error {
+    object_store::Error::NotSupported {
+        source: format!("{operation} is not allowed on a read-only store").into(),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ReadOnlyStore {
+    async fn put_opts(
+        &self,
+        _location: &Path,
+        _payload: PutPayload,
+        _opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        Err(read_only("put"))
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        _location: &Path,
+        _opts: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        Err(read_only("put"))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<u64>],
+    ) -> object_store::Result<Vec<Bytes>> {
+        self.inner.get_ranges(location, ranges).await
+    }
+
+    fn delete_stream(
+        &self,
+        locations: BoxStream<'static, object_store::Result<Path>>,
+    ) -> BoxStream<'static, object_store::Result<Path>> {
+        locations.map(|_| Err(read_only("delete"))).boxed()
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy_opts(
+        &self,
+        _from: &Path,
+        _to: &Path,
+        _options: CopyOptions,
+    ) -> object_store::Result<()> {
+        Err(read_only("copy"))
+    }
+
+    async fn rename_opts(
+        &self,
+        _from: &Path,
+        _to: &Path,
+        _options: RenameOptions,
+    ) -> object_store::Result<()> {
+        Err(read_only("rename"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use object_store::ObjectStoreExt;
+    use object_store::path::Path;
+
+    use super::build_http_store;
+    use crate::config::{UpstreamClientConfig, UpstreamRetryConfig};
+    use crate::metrics::Metrics;
+    use crate::store::client::UpstreamClient;
+
+    #[test]
+    fn rejects_invalid_header_name() {
+        let headers = HashMap::from([("bad header".to_string(), "x".to_string())]);
+        assert!(
+            build_http_store(
+                "cdn",
+                "https://cdn.example.com",
+                &headers,
+                false,
+                UpstreamClient::default(),
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_writes() {
+        let headers = HashMap::from([("x-api-key".to_string(), "secret".to_string())]);
+        let store = build_http_store(
+            "cdn",
+            "https://cdn.example.com",
+            &headers,
+            false,
+            UpstreamClient::default(),
+        )
+        .unwrap();
+
+        let result = store.put_multipart(&Path::from("a.txt")).await;
+        assert!(matches!(
+            result,
+            Err(object_store::Error::NotSupported { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn counts_retried_upstream_failures() {
+        let app = Router::new().route(
+            "/{*path}",
+            get(|| async { StatusCode::SERVICE_UNAVAILABLE }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let metrics = Arc::new(Metrics::new());
+        let retry = UpstreamRetryConfig {
+            max_retries: Some(2),
+            init_backoff: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let upstream = UpstreamClient::new(
+            "flaky",
+            &UpstreamClientConfig::default(),
+            &retry,
+            metrics.clone(),
+        );
+        let store = build_http_store(
+            "flaky",
+            &format!("http://{addr}"),
+            &HashMap::new(),
+            true,
+            upstream,
+        )
+        .unwrap();
+
+        assert!(store.get(&Path::from("a.txt")).await.is_err());
+        assert!(
+            metrics
+                .render_prometheus()
+                .contains(r#"cachegate_upstream_retry_total{reason="status",store="flaky"} 2"#)
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path as FsPath;
+use std::sync::Arc;
+
+use anyhow::Context;
+use object_store::memory::InMemory;
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreExt, PutPayload};
+use tracing::info;
+
+pub(crate) async fn build_memory_store(
+    store_id: &str,
+    seed_dir: Option<&str>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    let store = InMemory::new();
+
+    if let Some(seed_dir) = seed_dir {
+        // The directory walk is synchronous, so keep it off the runtime threads.
+        let root = seed_dir.to_string();
+        let files = tokio::task::spawn_blocking(move || {
+            let mut files = Vec::new();
+            collect_files(FsPath::new(&root), FsPath::new(&root), &mut files).map(|_| files)
+        })
+        .await?
+        .with_context(|| format!("failed to read seed dir {seed_dir} for store {store_id}"))?;
+
+        let mut bytes = 0usize;
+        for (key, file) in &files {
+            let contents = tokio::fs::read(file)
+                .await
+                .with_context(|| format!("failed to read seed file {}", file.display()))?;
+            bytes += contents.len();
+            store
+                .put(&Path::from(key.as_str()), PutPayload::from(contents))
+                .await?;
+        }
+        info!(
+            store_id = %store_id,
+            seed_dir = %seed_dir,
+            objects = files.len(),
+            bytes,
+            "memory store seeded"
+        );
+    }
+
+    Ok(Arc::new(store))
+}
+
+fn collect_files(
+    root: &FsPath,
+    dir: &FsPath,
+    files: &mut Vec<(String, std::path::PathBuf)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let key = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.push((key, path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::ObjectStoreExt;
+    use object_store::path::Path;
+    use tempfile::TempDir;
+
+    use super::build_memory_store;
+
+    #[tokio::test]
+    async fn starts_empty_without_seed_dir() {
+        let store = build_memory_store("mem", None).await.unwrap();
+        let result = store.head(&Path::from("missing.txt")).await;
+        assert!(matches!(result, Err(object_store::Error::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn seeds_nested_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("top.txt"), b"top").unwrap();
+        std::fs::write(dir.path().join("a/b/deep.bin"), b"deep").unwrap();
+
+        let seed_dir = dir.path().to_string_lossy().to_string();
+        let store = build_memory_store("mem", Some(&seed_dir)).await.unwrap();
+
+        let top = store.get(&Path::from("top.txt")).await.unwrap();
+        assert_eq!(top.bytes().await.unwrap().as_ref(), b"top");
+        let deep = store.get(&Path::from("a/b/deep.bin")).await.unwrap();
+        assert_eq!(deep.bytes().await.unwrap().as_ref(), b"deep");
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::{BoxStream, FuturesUnordered};
+use object_store::path::Path;
+use object_store::{
+    CopyOptions, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOptions, PutOptions, PutPayload, PutResult, RenameOptions,
+};
+use tracing::{info, warn};
+
+use crate::config::{MirrorStrategy, StoreConfig, StoreOptions};
+use crate::metrics::Metrics;
+
+pub(crate) async fn build_mirror_store(
+    store_id: &str,
+    configs: &[StoreConfig],
+    strategy: MirrorStrategy,
+    options: &StoreOptions,
+    metrics: &Arc<Metrics>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    if configs.is_empty() {
+        anyhow::bail!("store {store_id}: mirror needs at least one member");
+    }
+
+    let mut members = Vec::with_capacity(configs.len());
+    for (index, config) in configs.iter().enumerate() {
+        let store =
+            super::build_store(&format!("{store_id}[{index}]"), config, options, metrics).await?;
+        members.push(MirrorMember {
+            label: index.to_string(),
+            store,
+        });
+    }
+
+    info!(
+        store_id = %store_id,
+        members = members.len(),
+        strategy = ?strategy,
+        "mirror store initialized"
+    );
+
+    Ok(Arc::new(MirrorStore {
+        id: store_id.to_string(),
+        members,
+        strategy,
+        metrics: metrics.clone(),
+    }))
+}
+
+#[derive(Debug)]
+struct MirrorMember {
+    label: String,
+    store: Arc<dyn ObjectStore>,
+}
+
+/// Serves reads from an ordered list of equivalent stores, failing over on misses and errors.
+///
+/// Writes and listings go to the first member.
+#[derive(Debug)]
+struct MirrorStore {
+    id: String,
+    members: Vec<MirrorMember>,
+    strategy: MirrorStrategy,
+    metrics: Arc<Metrics>,
+}
+
+impl fmt::Display for MirrorStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mirror({})", self.id)
+    }
+}
+
+impl MirrorStore {
+    fn primary(&self) -> &Arc<dyn ObjectStore> {
+        &self.members[0].store
+    }
+
+    fn record<T>(&self, member: &MirrorMember, result: &object_store::Result<T>) {
+        let outcome = match result {
+            Err(object_store::Error::NotFound { .. }) => "not_found",
+            Err(err) if fails_over(err) => {
+                warn!(
+                    store_id = %self.id,
+                    member = %member.label,
+                    error = %err,
+                    "mirror member read failed"
+                );
+                "error"
+            }
+            _ => "ok",
+        };
+        self.metrics
+            .inc_mirror_member(&self.id, &member.label, outcome);
+    }
+
+    async fn get_ordered(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let mut failure = None;
+        for member in &self.members {
+            let result = member.store.get_opts(location, options.clone()).await;
+            self.record(member, &result);
+            match result {
+                Err(err) if fails_over(&err) => failure = Some(keep_worst(failure, err)),
+                other => return other,
+            }
+        }
+        Err(failure.unwrap_or_else(|| not_found(location)))
+    }
+
+    async fn get_parallel(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let mut pending = self
+            .members
+            .iter()
+            .map(|member| {
+                let options = options.clone();
+                async move { (member, member.store.get_opts(location, options).await) }
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut failure = None;
+        while let Some((member, result)) = pending.next().await {
+            self.record(member, &result);
+            match result {
+                Err(err) if fails_over(&err) => failure = Some(keep_worst(failure, err)),
+                other => return other,
+            }
+        }
+        Err(failure.unwrap_or_else(|| not_found(location)))
+    }
+}
+
+/// Conditional request outcomes are answers, not failures, so they are not retried elsewhere.
+fn fails_over(err: &object_store::Error) -> bool {
+    !matches!(
+        err,
+        object_store::Error::NotModified { .. } | object_store::Error::Precondition { .. }
+    )
+}
+
+/// Prefers a real error over NotFound, so an unreachable member is not reported as a miss.
+fn keep_worst(
+    current: Option<object_store::Error>,
+    next: object_store::Error,
+) -> object_store::Error {
+    match current {
+        Some(current) if !matches!(current, object_store::Error::NotFound { .. }) => current,
+        _ => next,
+    }
+}
+
+fn not_found(location: &Path) -> object_store::Error {
+    object_store::Error::NotFound {
+        path: location.to_string(),
+        source: "no mirror member holds the object".into(),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MirrorStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.primary().put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.primary().put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        match self.strategy {
+            MirrorStrategy::Ordered => self.get_ordered(location, options).await,
+            MirrorStrategy::Parallel => self.get_parallel(location, options).await,
+        }
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<u64>],
+    ) -> object_store::Result<Vec<Bytes>> {
+        let mut failure = None;
+        for member in &self.members {
+            let result = member.store.get_ranges(location, ranges).await;
+            self.record(member, &result);
+            match result {
+                Err(err) if fails_over(&err) => failure = Some(keep_worst(failure, err)),
+                other => return other,
+            }
+        }
+        Err(failure.unwrap_or_else(|| not_found(location)))
+    }
+
+    fn delete_stream(
+        &self,
+        locations: BoxStream<'static, object_store::Result<Path>>,
+    ) -> BoxStream<'static, object_store::Result<Path>> {
+        self.primary().delete_stream(locations)
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.primary().list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.primary().list_with_delimiter(prefix).await
+    }
+
+    async fn copy_opts(
+        &self,
+        from: &Path,
+        to: &Path,
+        options: CopyOptions,
+    ) -> object_store::Result<()> {
+        self.primary().copy_opts(from, to, options).await
+    }
+
+    async fn rename_opts(
+        &self,
+        from: &Path,
+        to: &Path,
+        options: RenameOptions,
+    ) -> object_store::Result<()> {
+        self.primary().rename_opts(from, to, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::ObjectStoreExt;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    fn mirror(strategy: MirrorStrategy) -> (Arc<InMemory>, Arc<InMemory>, MirrorStore) {
+        let primary = Arc::new(InMemory::new());
+        let replica = Arc::new(InMemory::new());
+        let store = MirrorStore {
+            id: "assets".to_string(),
+            members: vec![
+                MirrorMember {
+                    label: "0".to_string(),
+                    store: primary.clone(),
+                },
+                MirrorMember {
+                    label: "1".to_string(),
+                    store: replica.clone(),
+                },
+            ],
+            strategy,
+            metrics: Arc::new(Metrics::new()),
+        };
+        (primary, replica, store)
+    }
+
+    #[tokio::test]
+    async fn ordered_falls_through_to_replica() {
+        let (_, replica, store) = mirror(MirrorStrategy::Ordered);
+        let location = Path::from("a.txt");
+        replica.put(&location, "replica".into()).await.unwrap();
+
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"replica");
+
+        let rendered = store.metrics.render_prometheus();
+        assert!(rendered.contains(
+            r#"cachegate_mirror_member_total{member="0",result="not_found",store="assets"} 1"#
+        ));
+        assert!(
+            rendered.contains(
+                r#"cachegate_mirror_member_total{member="1",result="ok",store="assets"} 1"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn parallel_serves_any_member() {
+        let (_, replica, store) = mirror(MirrorStrategy::Parallel);
+        let location = Path::from("a.txt");
+        replica.put(&location, "replica".into()).await.unwrap();
+
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"replica");
+    }
+
+    #[tokio::test]
+    async fn missing_everywhere_is_not_found() {
+        let (_, _, store) = mirror(MirrorStrategy::Ordered);
+
+        let result = store.get(&Path::from("missing.txt")).await;
+        assert!(matches!(result, Err(object_store::Error::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn writes_go_to_primary() {
+        let (primary, replica, store) = mirror(MirrorStrategy::Ordered);
+        let location = Path::from("a.txt");
+
+        store.put(&location, "fresh".into()).await.unwrap();
+
+        assert!(primary.head(&location).await.is_ok());
+        assert!(replica.head(&location).await.is_err());
+    }
+}
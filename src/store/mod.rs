@@ -1,51 +1,265 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use anyhow::Context;
+use axum::http::Method;
 use object_store::ObjectStore;
-use object_store::aws::AmazonS3Builder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::prefix::PrefixStore;
 
 mod azure;
+mod client;
+mod gcs;
+pub mod guard;
+mod http;
+mod memory;
+mod mirror;
+mod replica;
+mod rewrite;
+mod s3;
+pub mod throttle;
 
-use crate::config::StoreConfig;
+use crate::config::{StoreConfig, StoreEntry, StoreOptions};
+use crate::metrics::Metrics;
+use crate::ready::ReadinessProbe;
+use client::UpstreamClient;
+use guard::UpstreamGuard;
+use rewrite::KeyRewriter;
+use throttle::Throttle;
 
-pub type StoreMap = HashMap<String, Arc<dyn ObjectStore>>;
+pub type StoreMap = HashMap<String, Arc<Store>>;
 
-pub fn build_stores(configs: &HashMap<String, StoreConfig>) -> anyhow::Result<StoreMap> {
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// A configured bucket: the backing object store plus how request paths map onto it.
+#[derive(Debug)]
+pub struct Store {
+    /// Backing store, already scoped to the configured prefix.
+    pub object_store: Arc<dyn ObjectStore>,
+    /// Concurrency limit and circuit breaker for requests that reach the upstream.
+    pub guard: UpstreamGuard,
+    pub policy: MethodPolicy,
+    pub readiness: ReadinessProbe,
+    /// Upstream bandwidth cap, if the store sets one.
+    pub throttle: Option<Throttle>,
+    /// Unique per built store; part of every cache key so a reload drops the old entries.
+    pub generation: u64,
+    rewriter: KeyRewriter,
+}
+
+/// Which request methods a store accepts, checked before any upstream call.
+#[derive(Debug, Clone, Copy)]
+pub struct MethodPolicy {
+    pub read: bool,
+    pub write: bool,
+    pub prefetch: bool,
+}
+
+impl MethodPolicy {
+    fn from_options(id: &str, options: &StoreOptions) -> anyhow::Result<Self> {
+        if options.read_only && options.write_only {
+            anyhow::bail!("store {id}: read_only and write_only are mutually exclusive");
+        }
+        Ok(Self {
+            read: !options.write_only,
+            write: !options.read_only,
+            prefetch: !options.write_only && !options.disable_prefetch,
+        })
+    }
+
+    pub fn allows(&self, method: &Method) -> bool {
+        match *method {
+            Method::GET | Method::HEAD => self.read,
+            Method::PUT => self.write,
+            _ => false,
+        }
+    }
+
+    /// The methods `allows` accepts, as an `Allow` header value.
+    pub fn allowed_methods(&self) -> String {
+        let mut methods = Vec::new();
+        if self.read {
+            methods.extend(["GET", "HEAD"]);
+        }
+        if self.write {
+            methods.push("PUT");
+        }
+        methods.join(", ")
+    }
+}
+
+impl Store {
+    /// Returns the object key for a request path, or `None` if it maps to an empty key.
+    pub fn location(&self, path: &str) -> Option<Path> {
+        self.rewriter.location(path)
+    }
+}
+
+pub async fn build_stores(
+    configs: &HashMap<String, StoreEntry>,
+    metrics: Arc<Metrics>,
+) -> anyhow::Result<StoreMap> {
     let mut stores: StoreMap = HashMap::new();
 
-    for (id, config) in configs {
-        let store: Arc<dyn ObjectStore> = match config {
-            StoreConfig::S3 {
-                bucket,
-                region,
-                access_key,
-                secret_key,
-                endpoint,
-                allow_http,
-            } => {
-                let mut builder = AmazonS3Builder::new()
-                    .with_bucket_name(bucket)
-                    .with_region(region)
-                    .with_access_key_id(access_key)
-                    .with_secret_access_key(secret_key);
-
-                if let Some(endpoint) = endpoint.as_deref() {
-                    builder = builder.with_endpoint(endpoint);
-                }
-                if allow_http.unwrap_or(false) {
-                    builder = builder.with_allow_http(true);
-                }
-
-                Arc::new(builder.build()?)
+    for (id, entry) in configs {
+        let mut object_store = match &entry.backend {
+            StoreConfig::Mirror { members, strategy } => {
+                mirror::build_mirror_store(id, members, *strategy, &entry.options, &metrics).await?
             }
-            StoreConfig::Azure {
-                container,
-                connection_string,
-            } => azure::build_azure_store(id, container, connection_string)?,
+            config => build_store(id, config, &entry.options, &metrics).await?,
         };
+        if let Some(replication) = &entry.options.replication {
+            object_store = replica::build_replicated_store(
+                id,
+                object_store,
+                replication,
+                &entry.options,
+                &metrics,
+            )
+            .await?;
+        }
+
+        let prefix = entry
+            .options
+            .prefix
+            .as_deref()
+            .map(Path::from)
+            .filter(|prefix| !prefix.as_ref().is_empty());
+        if let Some(prefix) = prefix {
+            object_store = Arc::new(PrefixStore::new(object_store, prefix));
+        }
+        let rewriter = KeyRewriter::new(&entry.options.rewrite)
+            .with_context(|| format!("invalid rewrite rules for store {id}"))?;
+        let policy = MethodPolicy::from_options(id, &entry.options)?;
+        let throttle = entry
+            .options
+            .bandwidth
+            .as_ref()
+            .map(|config| Throttle::new(id, config, metrics.clone()))
+            .transpose()?;
+        let guard = UpstreamGuard::new(
+            id,
+            entry.options.concurrency.as_ref(),
+            entry.options.breaker.as_ref(),
+            metrics.clone(),
+        );
 
-        stores.insert(id.clone(), store);
+        stores.insert(
+            id.clone(),
+            Arc::new(Store {
+                object_store,
+                guard,
+                policy,
+                readiness: ReadinessProbe::new(&entry.options.readiness),
+                throttle,
+                generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+                rewriter,
+            }),
+        );
     }
 
     Ok(stores)
 }
+
+async fn build_store(
+    id: &str,
+    config: &StoreConfig,
+    options: &StoreOptions,
+    metrics: &Arc<Metrics>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    let upstream = UpstreamClient::new(id, &options.client, &options.retry, metrics.clone());
+    let store: Arc<dyn ObjectStore> = match config {
+        StoreConfig::S3 {
+            bucket,
+            region,
+            access_key,
+            secret_key,
+            session_token,
+            endpoint,
+            allow_http,
+            role_arn,
+            role_session_name,
+            web_identity_token_file,
+            sts_endpoint,
+            metadata_endpoint,
+        } => s3::build_s3_store(
+            id,
+            s3::S3Options {
+                bucket,
+                region,
+                access_key: access_key.as_deref(),
+                secret_key: secret_key.as_deref(),
+                session_token: session_token.as_deref(),
+                endpoint: endpoint.as_deref(),
+                allow_http: allow_http.unwrap_or(false),
+                role_arn: role_arn.as_deref(),
+                role_session_name: role_session_name.as_deref(),
+                web_identity_token_file: web_identity_token_file.as_deref(),
+                sts_endpoint: sts_endpoint.as_deref(),
+                metadata_endpoint: metadata_endpoint.as_deref(),
+                upstream,
+            },
+        )?,
+        StoreConfig::Azure {
+            container,
+            connection_string,
+            account,
+            sas_token,
+            use_emulator,
+            client_id,
+            tenant_id,
+            federated_token_file,
+            msi_endpoint,
+        } => azure::build_azure_store(
+            id,
+            azure::AzureOptions {
+                container,
+                connection_string: connection_string.as_deref(),
+                account: account.as_deref(),
+                sas_token: sas_token.as_deref(),
+                use_emulator: use_emulator.unwrap_or(false),
+                client_id: client_id.as_deref(),
+                tenant_id: tenant_id.as_deref(),
+                federated_token_file: federated_token_file.as_deref(),
+                msi_endpoint: msi_endpoint.as_deref(),
+                upstream,
+            },
+        )?,
+        StoreConfig::Gcs {
+            bucket,
+            service_account_key,
+            service_account_path,
+            endpoint,
+            skip_signature,
+        } => gcs::build_gcs_store(
+            id,
+            gcs::GcsOptions {
+                bucket,
+                service_account_key: service_account_key.as_deref(),
+                service_account_path: service_account_path.as_deref(),
+                endpoint: endpoint.as_deref(),
+                skip_signature: skip_signature.unwrap_or(false),
+                upstream,
+            },
+        )?,
+        StoreConfig::Filesystem { root } => Arc::new(
+            LocalFileSystem::new_with_prefix(root)
+                .with_context(|| format!("invalid filesystem root {root} for store {id}"))?,
+        ),
+        StoreConfig::Memory { seed_dir } => {
+            memory::build_memory_store(id, seed_dir.as_deref()).await?
+        }
+        StoreConfig::Http {
+            url,
+            headers,
+            allow_http,
+        } => http::build_http_store(id, url, headers, allow_http.unwrap_or(false), upstream)?,
+        StoreConfig::Mirror { .. } => {
+            anyhow::bail!("store {id}: mirror stores cannot be nested")
+        }
+    };
+
+    Ok(store)
+}
@@ -0,0 +1,665 @@
+use std::fmt;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::future::join_all;
+use futures::stream::{BoxStream, FuturesUnordered};
+use object_store::path::Path;
+use object_store::{
+    CopyOptions, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOptions, PutOptions, PutPayload, PutResult, RenameOptions, UploadPart,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{ReplicationConfig, ReplicationPolicy, StoreOptions};
+use crate::metrics::Metrics;
+
+pub(crate) async fn build_replicated_store(
+    store_id: &str,
+    primary: Arc<dyn ObjectStore>,
+    config: &ReplicationConfig,
+    options: &StoreOptions,
+    metrics: &Arc<Metrics>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    if config.replicas.is_empty() {
+        anyhow::bail!("store {store_id}: replication needs at least one replica");
+    }
+    let targets = config.replicas.len() + 1;
+    let required = match (config.policy, config.quorum) {
+        (ReplicationPolicy::Quorum, quorum) => quorum.unwrap_or(targets / 2 + 1),
+        (_, Some(_)) => anyhow::bail!("store {store_id}: quorum only applies to the quorum policy"),
+        (ReplicationPolicy::All, None) => targets,
+        (ReplicationPolicy::Async, None) => 1,
+    };
+    if required == 0 || required > targets {
+        anyhow::bail!("store {store_id}: quorum must be between 1 and {targets}");
+    }
+
+    let mut replicas = Vec::with_capacity(config.replicas.len());
+    for (index, replica) in config.replicas.iter().enumerate() {
+        let id = format!("{store_id}.replicas[{index}]");
+        replicas.push(super::build_store(&id, replica, options, metrics).await?);
+    }
+
+    info!(
+        store_id = %store_id,
+        replicas = replicas.len(),
+        policy = ?config.policy,
+        required,
+        "replicated store initialized"
+    );
+
+    Ok(Arc::new(ReplicatedStore {
+        primary,
+        replicas,
+        replication: Arc::new(Replication {
+            id: store_id.to_string(),
+            policy: config.policy,
+            required,
+            metrics: metrics.clone(),
+        }),
+    }))
+}
+
+/// Copies writes from the primary to a set of replicas under a [`ReplicationPolicy`].
+///
+/// Reads, listings and deletes only touch the primary.
+#[derive(Debug)]
+struct ReplicatedStore {
+    primary: Arc<dyn ObjectStore>,
+    replicas: Vec<Arc<dyn ObjectStore>>,
+    replication: Arc<Replication>,
+}
+
+impl fmt::Display for ReplicatedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Replicated({})", self.replication.id)
+    }
+}
+
+impl ReplicatedStore {
+    fn targets(&self) -> impl Iterator<Item = &Arc<dyn ObjectStore>> {
+        std::iter::once(&self.primary).chain(&self.replicas)
+    }
+}
+
+/// A write to one target. Target 0 is the primary, the rest are replicas in config order.
+type Write = JoinHandle<object_store::Result<PutResult>>;
+
+#[derive(Debug)]
+struct Replication {
+    id: String,
+    policy: ReplicationPolicy,
+    /// Targets that must accept a write. The primary is always one of them, since reads only
+    /// go to the primary.
+    required: usize,
+    metrics: Arc<Metrics>,
+}
+
+impl Replication {
+    /// Whether a write waits on this target's parts, rather than running them in the background.
+    fn awaits(&self, target: usize) -> bool {
+        target == 0 || self.policy != ReplicationPolicy::Async
+    }
+
+    fn satisfied(&self, failed: &[bool]) -> bool {
+        match self.policy {
+            ReplicationPolicy::Async => !failed[0],
+            _ => !failed[0] && failed.iter().filter(|failed| !**failed).count() >= self.required,
+        }
+    }
+
+    /// Runs a target's write as its own task, so it can finish after the request stopped
+    /// waiting for it. Replica outcomes and lag are recorded when it does.
+    fn spawn<F>(&self, target: usize, primary_done: &Arc<OnceLock<Instant>>, write: F) -> Write
+    where
+        F: Future<Output = object_store::Result<PutResult>> + Send + 'static,
+    {
+        let id = self.id.clone();
+        let metrics = self.metrics.clone();
+        let primary_done = primary_done.clone();
+        tokio::spawn(async move {
+            let result = write.await;
+            if target == 0 {
+                if result.is_ok() {
+                    let _ = primary_done.set(Instant::now());
+                }
+                return result;
+            }
+
+            let replica = (target - 1).to_string();
+            match &result {
+                Ok(_) => {
+                    // Zero when the replica finished first or the primary failed.
+                    let lag = primary_done
+                        .get()
+                        .map(|done| done.elapsed().as_millis() as u64)
+                        .unwrap_or(0);
+                    metrics.observe_replication_lag_ms(&id, lag);
+                    metrics.inc_replication(&id, &replica, "ok");
+                }
+                Err(err) => {
+                    warn!(store_id = %id, replica = %replica, error = %err, "replica write failed");
+                    metrics.inc_replication(&id, &replica, "err");
+                }
+            }
+            result
+        })
+    }
+
+    /// Waits until the policy is met or can no longer be. Writes still running carry on in
+    /// the background.
+    async fn wait(&self, mut writes: Vec<Write>) -> object_store::Result<PutResult> {
+        if self.policy == ReplicationPolicy::Async {
+            let primary = writes.swap_remove(0);
+            return primary.await?;
+        }
+
+        let total = writes.len();
+        let mut pending = writes
+            .into_iter()
+            .enumerate()
+            .map(|(target, write)| async move { (target, write.await) })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut accepted: Option<PutResult> = None;
+        let mut primary_ok = false;
+        let mut succeeded = 0;
+        let mut failed = 0;
+        while let Some((target, result)) = pending.next().await {
+            match result
+                .map_err(object_store::Error::from)
+                .and_then(|result| result)
+            {
+                Ok(put) => {
+                    succeeded += 1;
+                    if target == 0 {
+                        primary_ok = true;
+                        accepted = Some(put);
+                    }
+                    if primary_ok && succeeded >= self.required {
+                        return Ok(accepted.take().expect("the primary succeeded"));
+                    }
+                }
+                Err(err) => {
+                    failed += 1;
+                    if target == 0 || failed > total - self.required {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        unreachable!("every write finished without meeting or failing the policy")
+    }
+}
+
+fn part_failed() -> object_store::Error {
+    object_store::Error::Generic {
+        store: "Replicated",
+        source: "an earlier part upload failed".into(),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ReplicatedStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        let primary_done = Arc::new(OnceLock::new());
+        let writes = self
+            .targets()
+            .enumerate()
+            .map(|(target, store)| {
+                let store = store.clone();
+                let location = location.clone();
+                let payload = payload.clone();
+                let opts = opts.clone();
+                self.replication.spawn(target, &primary_done, async move {
+                    store.put_opts(&location, payload, opts).await
+                })
+            })
+            .collect();
+        self.replication.wait(writes).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        let started = join_all(
+            self.targets()
+                .map(|store| store.put_multipart_opts(location, opts.clone())),
+        )
+        .await;
+
+        let mut targets = Vec::with_capacity(started.len());
+        let mut failure = None;
+        for (target, result) in started.into_iter().enumerate() {
+            let upload = match result {
+                Ok(upload) => Some(upload),
+                Err(err) => {
+                    if target > 0 {
+                        warn!(
+                            store_id = %self.replication.id,
+                            replica = target - 1,
+                            error = %err,
+                            "replica upload init failed"
+                        );
+                    }
+                    failure.get_or_insert(err);
+                    None
+                }
+            };
+            targets.push(UploadTarget {
+                failed: Arc::new(AtomicBool::new(upload.is_none())),
+                upload,
+                parts: Vec::new(),
+            });
+        }
+
+        let mut upload = ReplicatedUpload {
+            replication: self.replication.clone(),
+            targets,
+        };
+        let failed = upload.failed();
+        if let Some(err) = failure
+            && !self.replication.satisfied(&failed)
+        {
+            // Completion never runs, so count the replicas that did not start here.
+            for (replica, _) in failed[1..]
+                .iter()
+                .enumerate()
+                .filter(|(_, failed)| **failed)
+            {
+                self.replication.metrics.inc_replication(
+                    &self.replication.id,
+                    &replica.to_string(),
+                    "err",
+                );
+            }
+            let _ = upload.abort().await;
+            return Err(err);
+        }
+        Ok(Box::new(upload))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.primary.get_opts(location, options).await
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<u64>],
+    ) -> object_store::Result<Vec<Bytes>> {
+        self.primary.get_ranges(location, ranges).await
+    }
+
+    fn delete_stream(
+        &self,
+        locations: BoxStream<'static, object_store::Result<Path>>,
+    ) -> BoxStream<'static, object_store::Result<Path>> {
+        self.primary.delete_stream(locations)
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.primary.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.primary.list_with_delimiter(prefix).await
+    }
+
+    async fn copy_opts(
+        &self,
+        from: &Path,
+        to: &Path,
+        options: CopyOptions,
+    ) -> object_store::Result<()> {
+        self.primary.copy_opts(from, to, options).await
+    }
+
+    async fn rename_opts(
+        &self,
+        from: &Path,
+        to: &Path,
+        options: RenameOptions,
+    ) -> object_store::Result<()> {
+        self.primary.rename_opts(from, to, options).await
+    }
+}
+
+#[derive(Debug)]
+struct UploadTarget {
+    /// `None` once the upload could not be started, or was handed off to complete or abort.
+    upload: Option<Box<dyn MultipartUpload>>,
+    /// Parts running in the background for targets the request does not wait on.
+    parts: Vec<JoinHandle<object_store::Result<()>>>,
+    failed: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct ReplicatedUpload {
+    replication: Arc<Replication>,
+    targets: Vec<UploadTarget>,
+}
+
+impl ReplicatedUpload {
+    fn failed(&self) -> Vec<bool> {
+        self.targets
+            .iter()
+            .map(|target| target.failed.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl MultipartUpload for ReplicatedUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        let mut awaited = Vec::new();
+        for (index, target) in self.targets.iter_mut().enumerate() {
+            let Some(upload) = target.upload.as_mut() else {
+                continue;
+            };
+            if target.failed.load(Ordering::Relaxed) {
+                continue;
+            }
+            let failed = target.failed.clone();
+            let part = upload.put_part(data.clone());
+            let part = async move {
+                let result = part.await;
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                result
+            };
+            if self.replication.awaits(index) {
+                awaited.push(part);
+            } else {
+                target.parts.push(tokio::spawn(part));
+            }
+        }
+
+        let replication = self.replication.clone();
+        let flags: Vec<_> = self
+            .targets
+            .iter()
+            .map(|target| target.failed.clone())
+            .collect();
+        Box::pin(async move {
+            let results = join_all(awaited).await;
+            let failed: Vec<bool> = flags
+                .iter()
+                .map(|flag| flag.load(Ordering::Relaxed))
+                .collect();
+            if replication.satisfied(&failed) {
+                return Ok(());
+            }
+            Err(results
+                .into_iter()
+                .find_map(Result::err)
+                .unwrap_or_else(part_failed))
+        })
+    }
+
+    async fn complete(&mut self) -> object_store::Result<PutResult> {
+        let primary_done = Arc::new(OnceLock::new());
+        let writes = self
+            .targets
+            .iter_mut()
+            .enumerate()
+            .map(|(index, target)| {
+                let upload = target.upload.take();
+                let parts = std::mem::take(&mut target.parts);
+                let failed = target.failed.clone();
+                self.replication.spawn(index, &primary_done, async move {
+                    let mut upload = upload.ok_or_else(part_failed)?;
+                    for part in parts {
+                        if let Err(err) = part
+                            .await
+                            .map_err(object_store::Error::from)
+                            .and_then(|r| r)
+                        {
+                            let _ = upload.abort().await;
+                            return Err(err);
+                        }
+                    }
+                    if failed.load(Ordering::Relaxed) {
+                        let _ = upload.abort().await;
+                        return Err(part_failed());
+                    }
+                    let result = upload.complete().await;
+                    if result.is_err() {
+                        let _ = upload.abort().await;
+                    }
+                    result
+                })
+            })
+            .collect();
+        self.replication.wait(writes).await
+    }
+
+    async fn abort(&mut self) -> object_store::Result<()> {
+        let mut primary = Ok(());
+        for (index, target) in self.targets.iter_mut().enumerate() {
+            for part in target.parts.drain(..) {
+                part.abort();
+            }
+            if let Some(mut upload) = target.upload.take() {
+                let result = upload.abort().await;
+                if index == 0 {
+                    primary = result;
+                }
+            }
+        }
+        primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use object_store::{ObjectStoreExt, WriteMultipart};
+
+    use super::*;
+
+    /// Rejects every write, to stand in for an unreachable replica.
+    #[derive(Debug)]
+    struct Unwritable(InMemory);
+
+    impl fmt::Display for Unwritable {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Unwritable")
+        }
+    }
+
+    fn unwritable() -> object_store::Error {
+        object_store::Error::Generic {
+            store: "Unwritable",
+            source: "connection refused".into(),
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for Unwritable {
+        async fn put_opts(
+            &self,
+            _location: &Path,
+            _payload: PutPayload,
+            _opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            Err(unwritable())
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            _location: &Path,
+            _opts: PutMultipartOptions,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            Err(unwritable())
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            self.0.get_opts(location, options).await
+        }
+
+        fn delete_stream(
+            &self,
+            locations: BoxStream<'static, object_store::Result<Path>>,
+        ) -> BoxStream<'static, object_store::Result<Path>> {
+            self.0.delete_stream(locations)
+        }
+
+        fn list(
+            &self,
+            prefix: Option<&Path>,
+        ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+            self.0.list(prefix)
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.0.list_with_delimiter(prefix).await
+        }
+
+        async fn copy_opts(
+            &self,
+            from: &Path,
+            to: &Path,
+            options: CopyOptions,
+        ) -> object_store::Result<()> {
+            self.0.copy_opts(from, to, options).await
+        }
+    }
+
+    fn replicated(
+        policy: ReplicationPolicy,
+        required: usize,
+        replicas: Vec<Arc<dyn ObjectStore>>,
+    ) -> (Arc<InMemory>, ReplicatedStore) {
+        let primary = Arc::new(InMemory::new());
+        let store = ReplicatedStore {
+            primary: primary.clone(),
+            replicas,
+            replication: Arc::new(Replication {
+                id: "assets".to_string(),
+                policy,
+                required,
+                metrics: Arc::new(Metrics::new()),
+            }),
+        };
+        (primary, store)
+    }
+
+    async fn upload(store: &ReplicatedStore, location: &Path) -> object_store::Result<()> {
+        let upload = store.put_multipart(location).await?;
+        let mut write = WriteMultipart::new_with_chunk_size(upload, 4);
+        write.write(b"replicated payload");
+        write.finish().await.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn all_policy_writes_every_target() {
+        let replica = Arc::new(InMemory::new());
+        let (primary, store) = replicated(ReplicationPolicy::All, 2, vec![replica.clone()]);
+        let location = Path::from("a.txt");
+
+        upload(&store, &location).await.unwrap();
+
+        for target in [&primary, &replica] {
+            let bytes = target.get(&location).await.unwrap().bytes().await.unwrap();
+            assert_eq!(bytes.as_ref(), b"replicated payload");
+        }
+        let rendered = store.replication.metrics.render_prometheus();
+        assert!(
+            rendered.contains(
+                r#"cachegate_replication_total{replica="0",result="ok",store="assets"} 1"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn all_policy_fails_when_a_replica_fails() {
+        let broken: Arc<dyn ObjectStore> = Arc::new(Unwritable(InMemory::new()));
+        let (_, store) = replicated(ReplicationPolicy::All, 2, vec![broken]);
+
+        assert!(upload(&store, &Path::from("a.txt")).await.is_err());
+        assert!(store.put(&Path::from("b.txt"), "x".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn quorum_tolerates_a_failed_replica() {
+        let replica = Arc::new(InMemory::new());
+        let broken: Arc<dyn ObjectStore> = Arc::new(Unwritable(InMemory::new()));
+        let (primary, store) =
+            replicated(ReplicationPolicy::Quorum, 2, vec![replica.clone(), broken]);
+        let location = Path::from("a.txt");
+
+        store.put(&location, "quorum".into()).await.unwrap();
+        upload(&store, &Path::from("b.txt")).await.unwrap();
+
+        assert!(primary.head(&location).await.is_ok());
+        assert!(replica.head(&location).await.is_ok());
+        assert!(replica.head(&Path::from("b.txt")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn quorum_requires_the_primary() {
+        let replicas: Vec<Arc<dyn ObjectStore>> =
+            vec![Arc::new(InMemory::new()), Arc::new(InMemory::new())];
+        let store = ReplicatedStore {
+            primary: Arc::new(Unwritable(InMemory::new())),
+            replicas,
+            replication: Arc::new(Replication {
+                id: "assets".to_string(),
+                policy: ReplicationPolicy::Quorum,
+                required: 2,
+                metrics: Arc::new(Metrics::new()),
+            }),
+        };
+
+        assert!(store.put(&Path::from("a.txt"), "x".into()).await.is_err());
+        assert!(upload(&store, &Path::from("b.txt")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn async_policy_replicates_in_background() {
+        let replica = Arc::new(InMemory::new());
+        let broken: Arc<dyn ObjectStore> = Arc::new(Unwritable(InMemory::new()));
+        let (primary, store) =
+            replicated(ReplicationPolicy::Async, 1, vec![replica.clone(), broken]);
+        let location = Path::from("a.txt");
+
+        upload(&store, &location).await.unwrap();
+        assert!(primary.head(&location).await.is_ok());
+
+        for _ in 0..50 {
+            if replica.head(&location).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(replica.head(&location).await.is_ok());
+    }
+}
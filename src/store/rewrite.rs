@@ -0,0 +1,101 @@
+use anyhow::Context;
+use object_store::path::Path;
+use regex::Regex;
+
+use crate::config::RewriteRule;
+
+/// Maps request paths to object keys with ordered regex rewrite rules.
+#[derive(Debug, Default)]
+pub struct KeyRewriter {
+    rules: Vec<(Regex, String)>,
+}
+
+impl KeyRewriter {
+    pub fn new(rules: &[RewriteRule]) -> anyhow::Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern)
+                    .with_context(|| format!("invalid rewrite pattern {}", rule.pattern))?;
+                Ok((pattern, rule.template.clone()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns the object key for a request path, or `None` if it maps to an empty key.
+    ///
+    /// `.` and `..` segments are escaped by [`Path`], so neither the request nor a rewrite
+    /// template can climb out of the store prefix.
+    pub fn location(&self, path: &str) -> Option<Path> {
+        let mut key = None;
+        for (pattern, template) in &self.rules {
+            if let Some(captures) = pattern.captures(path) {
+                let mut rewritten = String::new();
+                captures.expand(template, &mut rewritten);
+                key = Some(rewritten);
+                break;
+            }
+        }
+
+        let location = Path::from(key.as_deref().unwrap_or(path));
+        (!location.as_ref().is_empty()).then_some(location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, template: &str) -> RewriteRule {
+        RewriteRule {
+            pattern: pattern.to_string(),
+            template: template.to_string(),
+        }
+    }
+
+    #[test]
+    fn passes_through_without_rules() {
+        let rewriter = KeyRewriter::default();
+
+        assert_eq!(rewriter.location("a/b.txt").unwrap().as_ref(), "a/b.txt");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rewriter = KeyRewriter::new(&[
+            rule(r"^img/(?P<id>[^/]+)\.jpg$", "images/$id/original.jpg"),
+            rule(r"^img/(.+)$", "fallback/$1"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            rewriter.location("img/42.jpg").unwrap().as_ref(),
+            "images/42/original.jpg"
+        );
+        assert_eq!(
+            rewriter.location("img/42.png").unwrap().as_ref(),
+            "fallback/42.png"
+        );
+        assert_eq!(rewriter.location("other").unwrap().as_ref(), "other");
+    }
+
+    #[test]
+    fn escapes_traversal_segments() {
+        let rewriter = KeyRewriter::new(&[rule(r"^up/(.+)$", "../$1")]).unwrap();
+
+        assert_eq!(
+            rewriter.location("up/secret").unwrap().as_ref(),
+            "%2E%2E/secret"
+        );
+        assert_eq!(
+            rewriter.location("../../etc/passwd").unwrap().as_ref(),
+            "%2E%2E/%2E%2E/etc/passwd"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_pattern() {
+        assert!(KeyRewriter::new(&[rule("(", "x")]).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use axum::http::{Method, Request};
+use object_store::aws::{
+    AmazonS3Builder, AmazonS3ConfigKey, AwsAuthorizer, AwsCredential, AwsCredentialProvider,
+};
+use object_store::client::{HttpClient, HttpConnector, HttpRequestBody};
+use object_store::{CredentialProvider, ObjectStore, RetryConfig};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::{info, info_span, warn};
+
+use super::client::{UpstreamClient, retry_reason};
+
+const ASSUME_ROLE_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+/// Characters left unescaped in SigV4 query strings.
+const STS_QUERY: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+const DEFAULT_SESSION_NAME: &str = "cachegate";
+
+pub(crate) struct S3Options<'a> {
+    pub bucket: &'a str,
+    pub region: &'a str,
+    pub access_key: Option<&'a str>,
+    pub secret_key: Option<&'a str>,
+    pub session_token: Option<&'a str>,
+    pub endpoint: Option<&'a str>,
+    pub allow_http: bool,
+    pub role_arn: Option<&'a str>,
+    pub role_session_name: Option<&'a str>,
+    pub web_identity_token_file: Option<&'a str>,
+    pub sts_endpoint: Option<&'a str>,
+    pub metadata_endpoint: Option<&'a str>,
+    pub upstream: UpstreamClient,
+}
+
+impl S3Options<'_> {
+    /// Mirrors the builder, where static keys win over a web identity token file.
+    fn credential_source(&self) -> &'static str {
+        match (
+            self.access_key.is_some(),
+            self.web_identity_token_file.is_some(),
+            self.role_arn.is_some(),
+        ) {
+            (true, true, _) => "static",
+            (false, true, _) => "web_identity",
+            (_, false, true) => "assume_role",
+            (true, false, false) => "static",
+            (false, false, false) => "environment",
+        }
+    }
+}
+
+pub(crate) fn build_s3_store(
+    store_id: &str,
+    options: S3Options<'_>,
+) -> anyhow::Result<Arc<dyn ObjectStore>> {
+    let span = info_span!(
+        "s3_store_init",
+        store_id = %store_id,
+        bucket = %options.bucket,
+        region = %options.region,
+        endpoint = %options.endpoint.unwrap_or("default"),
+        credentials = options.credential_source()
+    );
+    let _enter = span.enter();
+
+    // Without static keys, start from the AWS_* environment so the standard chain
+    // (env, web identity, ECS task role, instance metadata) resolves credentials.
+    let mut builder = match (options.access_key, options.secret_key) {
+        (Some(access_key), Some(secret_key)) => AmazonS3Builder::new()
+            .with_access_key_id(access_key)
+            .with_secret_access_key(secret_key),
+        (None, None) => AmazonS3Builder::from_env(),
+        _ => anyhow::bail!("store {store_id}: access_key and secret_key must be set together"),
+    };
+    for (key, value) in options.upstream.settings() {
+        builder = builder.with_config(AmazonS3ConfigKey::Client(key), value);
+    }
+    builder = builder
+        .with_bucket_name(options.bucket)
+        .with_region(options.region)
+        .with_retry(options.upstream.retry())
+        .with_http_connector(options.upstream.connector());
+
+    if let Some(token) = options.session_token {
+        builder = builder.with_token(token);
+    }
+    if let Some(endpoint) = options.endpoint {
+        builder = builder.with_endpoint(endpoint);
+    }
+    if options.allow_http {
+        builder = builder.with_allow_http(true);
+    }
+    if let Some(metadata_endpoint) = options.metadata_endpoint {
+        builder = builder.with_metadata_endpoint(metadata_endpoint);
+    }
+    if let Some(sts_endpoint) = options.sts_endpoint {
+        builder = builder.with_config(AmazonS3ConfigKey::StsEndpoint, sts_endpoint);
+    }
+    if let Some(session_name) = options.role_session_name {
+        builder = builder.with_config(AmazonS3ConfigKey::RoleSessionName, session_name);
+    }
+
+    if let Some(token_file) = options.web_identity_token_file {
+        let Some(role_arn) = options.role_arn else {
+            anyhow::bail!("store {store_id}: web_identity_token_file requires role_arn");
+        };
+        if options.access_key.is_some() {
+            warn!(store_id = %store_id, "static keys are set, ignoring web_identity_token_file");
+        }
+        builder = builder
+            .with_config(AmazonS3ConfigKey::WebIdentityTokenFile, token_file)
+            .with_config(AmazonS3ConfigKey::RoleArn, role_arn);
+    } else if let Some(role_arn) = options.role_arn {
+        let base = builder.clone().build()?.credentials().clone();
+        let sts_endpoint = options
+            .sts_endpoint
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("https://sts.{}.amazonaws.com", options.region));
+        let provider = AssumeRoleProvider::new(
+            base,
+            sts_endpoint,
+            options.region,
+            role_arn,
+            options.role_session_name.unwrap_or(DEFAULT_SESSION_NAME),
+            &options.upstream,
+        )?;
+        info!(store_id = %store_id, role_arn = %role_arn, "assuming role for s3 store");
+        builder = builder.with_credentials(Arc::new(provider));
+    }
+
+    Ok(Arc::new(builder.build()?))
+}
+
+/// Exchanges base credentials for temporary ones via STS `AssumeRole`, caching them until
+/// shortly before the `Expiration` STS returns. The session lasts as long as the role allows
+/// by default. STS calls use the store's upstream client and retry settings.
+#[derive(Debug)]
+struct AssumeRoleProvider {
+    base: AwsCredentialProvider,
+    client: HttpClient,
+    retry: RetryConfig,
+    endpoint: String,
+    region: String,
+    role_arn: String,
+    session_name: String,
+    cached: Mutex<Option<(Arc<AwsCredential>, Instant)>>,
+}
+
+impl AssumeRoleProvider {
+    fn new(
+        base: AwsCredentialProvider,
+        endpoint: String,
+        region: &str,
+        role_arn: &str,
+        session_name: &str,
+        upstream: &UpstreamClient,
+    ) -> anyhow::Result<Self> {
+        let client_options = upstream
+            .options()
+            .with_allow_http(endpoint.starts_with("http://"));
+        let client = upstream.connector().connect(&client_options)?;
+        Ok(Self {
+            base,
+            client,
+            retry: upstream.retry(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: region.to_string(),
+            role_arn: role_arn.to_string(),
+            session_name: session_name.to_string(),
+            cached: Mutex::new(None),
+        })
+    }
+
+    async fn assume_role(&self) -> Result<(AwsCredential, SystemTime), String> {
+        let base = self
+            .base
+            .get_credential()
+            .await
+            .map_err(|err| format!("failed to resolve base credentials: {err}"))?;
+
+        let uri = format!(
+            "{}/?Action=AssumeRole&Version=2011-06-15&RoleArn={}&RoleSessionName={}",
+            self.endpoint,
+            utf8_percent_encode(&self.role_arn, STS_QUERY),
+            utf8_percent_encode(&self.session_name, STS_QUERY),
+        );
+        let mut request = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(HttpRequestBody::empty())
+            .map_err(|err| format!("invalid sts request: {err}"))?;
+        AwsAuthorizer::new(&base, "sts", &self.region).authorize(&mut request, None);
+
+        let start = Instant::now();
+        let mut retries = 0;
+        let mut backoff = self.retry.backoff.init_backoff;
+        let response = loop {
+            let result = self.client.execute(request.clone()).await;
+            if retry_reason(&result).is_none()
+                || retries >= self.retry.max_retries
+                || start.elapsed() > self.retry.retry_timeout
+            {
+                break result;
+            }
+            retries += 1;
+            tokio::time::sleep(backoff).await;
+            backoff = backoff
+                .mul_f64(self.retry.backoff.base)
+                .min(self.retry.backoff.max_backoff);
+        }
+        .map_err(|err| format!("sts request failed: {err}"))?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .bytes()
+            .await
+            .map_err(|err| format!("failed to read sts response: {err}"))?;
+        let body = String::from_utf8_lossy(&body);
+        if !status.is_success() {
+            return Err(format!("sts returned {status}: {body}"));
+        }
+
+        parse_assume_role_response(&body)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AssumeRoleResponse {
+    assume_role_result: AssumeRoleResult,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AssumeRoleResult {
+    credentials: StsCredentials,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    expiration: String,
+}
+
+fn parse_assume_role_response(body: &str) -> Result<(AwsCredential, SystemTime), String> {
+    let response: AssumeRoleResponse =
+        quick_xml::de::from_str(body).map_err(|err| format!("invalid sts response: {err}"))?;
+    let credentials = response.assume_role_result.credentials;
+    let expiration = humantime::parse_rfc3339_weak(&credentials.expiration)
+        .map_err(|err| format!("invalid sts expiration {}: {err}", credentials.expiration))?;
+    Ok((
+        AwsCredential {
+            key_id: credentials.access_key_id,
+            secret_key: credentials.secret_access_key,
+            token: Some(credentials.session_token),
+        },
+        expiration,
+    ))
+}
+
+/// How long credentials valid for `lifetime` are cached: until the refresh margin, or half
+/// the lifetime for sessions shorter than twice the margin.
+fn refresh_after(lifetime: Duration) -> Duration {
+    lifetime.saturating_sub(ASSUME_ROLE_REFRESH_MARGIN.min(lifetime / 2))
+}
+
+#[async_trait]
+impl CredentialProvider for AssumeRoleProvider {
+    type Credential = AwsCredential;
+
+    async fn get_credential(&self) -> object_store::Result<Arc<AwsCredential>> {
+        let mut cached = self.cached.lock().await;
+        if let Some((credential, refresh_at)) = cached.as_ref()
+            && Instant::now() < *refresh_at
+        {
+            return Ok(Arc::clone(credential));
+        }
+
+        let (credential, expiration) =
+            self.assume_role()
+                .await
+                .map_err(|message| object_store::Error::Generic {
+                    store: "S3",
+                    source: message.into(),
+                })?;
+        let credential = Arc::new(credential);
+        let lifetime = expiration
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        let refresh_at = Instant::now() + refresh_after(lifetime);
+        *cached = Some((Arc::clone(&credential), refresh_at));
+        Ok(credential)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::Router;
+    use axum::extract::{Query, State};
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use object_store::StaticCredentialProvider;
+
+    use super::*;
+
+    fn options<'a>() -> S3Options<'a> {
+        S3Options {
+            bucket: "cachegate",
+            region: "us-east-1",
+            access_key: None,
+            secret_key: None,
+            session_token: None,
+            endpoint: None,
+            allow_http: false,
+            role_arn: None,
+            role_session_name: None,
+            web_identity_token_file: None,
+            sts_endpoint: None,
+            metadata_endpoint: None,
+            upstream: UpstreamClient::default(),
+        }
+    }
+
+    #[test]
+    fn credential_source_follows_builder_precedence() {
+        let mut options = options();
+        assert_eq!(options.credential_source(), "environment");
+        options.role_arn = Some("arn:aws:iam::123456789012:role/cachegate");
+        assert_eq!(options.credential_source(), "assume_role");
+        options.web_identity_token_file = Some("/var/run/token");
+        assert_eq!(options.credential_source(), "web_identity");
+        options.access_key = Some("AKIA");
+        options.secret_key = Some("secret");
+        assert_eq!(options.credential_source(), "static");
+        options.web_identity_token_file = None;
+        assert_eq!(options.credential_source(), "assume_role");
+    }
+
+    #[test]
+    fn rejects_partial_static_keys() {
+        let options = S3Options {
+            access_key: Some("AKIA"),
+            ..options()
+        };
+        assert!(build_s3_store("s3", options).is_err());
+    }
+
+    #[test]
+    fn rejects_web_identity_without_role() {
+        let options = S3Options {
+            web_identity_token_file: Some("/var/run/secrets/token"),
+            ..options()
+        };
+        assert!(build_s3_store("s3", options).is_err());
+    }
+
+    #[test]
+    fn builds_with_credential_chain() {
+        let options = S3Options {
+            metadata_endpoint: Some("http://127.0.0.1:1338"),
+            ..options()
+        };
+        assert!(build_s3_store("s3", options).is_ok());
+    }
+
+    fn sts_response(expiration: SystemTime) -> String {
+        format!(
+            concat!(
+                "<AssumeRoleResponse xmlns=\"https://sts.amazonaws.com/doc/2011-06-15/\">",
+                "<AssumeRoleResult><AssumedRoleUser><Arn>arn</Arn></AssumedRoleUser>",
+                "<Credentials>",
+                "<AccessKeyId>ASIATEMP</AccessKeyId>",
+                "<SecretAccessKey>temp-secret</SecretAccessKey>",
+                "<SessionToken>temp-token</SessionToken>",
+                "<Expiration>{}</Expiration>",
+                "</Credentials></AssumeRoleResult>",
+                "<ResponseMetadata><RequestId>1</RequestId></ResponseMetadata>",
+                "</AssumeRoleResponse>"
+            ),
+            humantime::format_rfc3339_seconds(expiration)
+        )
+    }
+
+    #[test]
+    fn parses_sts_credentials_and_expiration() {
+        let expiration = SystemTime::UNIX_EPOCH + Duration::from_secs(1_900_000_000);
+        let (credential, parsed) = parse_assume_role_response(&sts_response(expiration)).unwrap();
+
+        assert_eq!(credential.key_id, "ASIATEMP");
+        assert_eq!(credential.token.as_deref(), Some("temp-token"));
+        assert_eq!(parsed, expiration);
+        assert!(parse_assume_role_response("<AssumeRoleResponse/>").is_err());
+    }
+
+    #[test]
+    fn refreshes_before_expiration() {
+        assert_eq!(
+            refresh_after(Duration::from_secs(3600)),
+            Duration::from_secs(3300)
+        );
+        assert_eq!(
+            refresh_after(Duration::from_secs(300)),
+            Duration::from_secs(150)
+        );
+        assert_eq!(refresh_after(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn assume_role_exchanges_and_caches_credentials() {
+        #[derive(Clone, Default)]
+        struct StsState {
+            calls: Arc<AtomicUsize>,
+        }
+
+        async fn sts(
+            State(state): State<StsState>,
+            Query(params): Query<HashMap<String, String>>,
+            headers: HeaderMap,
+        ) -> String {
+            state.calls.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(params.get("Action").map(String::as_str), Some("AssumeRole"));
+            assert_eq!(
+                params.get("RoleArn").map(String::as_str),
+                Some("arn:aws:iam::123456789012:role/cachegate")
+            );
+            assert!(headers.contains_key("authorization"));
+            sts_response(SystemTime::now() + Duration::from_secs(3600))
+        }
+
+        let state = StsState::default();
+        let app = Router::new().route("/", get(sts)).with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let base: AwsCredentialProvider = Arc::new(StaticCredentialProvider::new(AwsCredential {
+            key_id: "AKIABASE".to_string(),
+            secret_key: "base-secret".to_string(),
+            token: None,
+        }));
+        let provider = AssumeRoleProvider::new(
+            base,
+            format!("http://{addr}"),
+            "us-east-1",
+            "arn:aws:iam::123456789012:role/cachegate",
+            DEFAULT_SESSION_NAME,
+            &UpstreamClient::default(),
+        )
+        .unwrap();
+
+        let first = provider.get_credential().await.unwrap();
+        assert_eq!(first.key_id, "ASIATEMP");
+        assert_eq!(first.secret_key, "temp-secret");
+        assert_eq!(first.token.as_deref(), Some("temp-token"));
+
+        let second = provider.get_credential().await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(state.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::BandwidthConfig;
+use crate::metrics::Metrics;
+
+/// Token bucket capping a store's upstream bandwidth, shared by reads and uploads.
+///
+/// Transfers take their bytes up front and may drive the bucket into debt; later transfers
+/// then wait for the debt to be repaid. Chunks larger than the burst still go through, just
+/// paced behind whatever is queued before them.
+#[derive(Debug)]
+pub struct Throttle {
+    store_id: String,
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+    metrics: Arc<Metrics>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Throttle {
+    pub fn new(
+        store_id: &str,
+        config: &BandwidthConfig,
+        metrics: Arc<Metrics>,
+    ) -> anyhow::Result<Self> {
+        if config.bytes_per_sec.as_u64() == 0 {
+            anyhow::bail!("store {store_id}: bandwidth bytes_per_sec must be greater than 0");
+        }
+        let rate = config.bytes_per_sec.as_u64() as f64;
+        let burst = config
+            .burst
+            .map(|burst| burst.as_u64() as f64)
+            .unwrap_or(rate);
+        Ok(Self {
+            store_id: store_id.to_string(),
+            rate,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+            metrics,
+        })
+    }
+
+    /// Waits until `bytes` fit in the bandwidth budget. `direction` labels the metrics.
+    pub async fn consume(&self, bytes: usize, direction: &str) {
+        let wait = self.reserve(bytes as f64, Instant::now());
+        if !wait.is_zero() {
+            let _waiting = Waiting::new(self);
+            tokio::time::sleep(wait).await;
+        }
+        self.metrics.observe_throttle(
+            &self.store_id,
+            direction,
+            bytes as u64,
+            wait.as_millis() as u64,
+        );
+    }
+
+    fn reserve(&self, bytes: f64, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst) - bytes;
+        bucket.updated = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+}
+
+/// Counts a transfer in the waiting gauge until dropped, so cancelled requests leave it too.
+struct Waiting<'a>(&'a Throttle);
+
+impl<'a> Waiting<'a> {
+    fn new(throttle: &'a Throttle) -> Self {
+        throttle.metrics.inc_throttle_waiting(&throttle.store_id);
+        Self(throttle)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.metrics.dec_throttle_waiting(&self.0.store_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytesize::ByteSize;
+
+    use super::*;
+
+    fn throttle(rate: u64, burst: u64) -> Throttle {
+        Throttle::new(
+            "s3",
+            &BandwidthConfig {
+                bytes_per_sec: ByteSize::b(rate),
+                burst: Some(ByteSize::b(burst)),
+            },
+            Arc::new(Metrics::new()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_sizes_and_rejects_zero_rate() {
+        let config: BandwidthConfig =
+            serde_yaml::from_str("bytes_per_sec: 10MiB\nburst: 256KiB").unwrap();
+        assert_eq!(config.bytes_per_sec, ByteSize::mib(10));
+        assert_eq!(config.burst, Some(ByteSize::kib(256)));
+
+        let config: BandwidthConfig = serde_yaml::from_str("bytes_per_sec: 0").unwrap();
+        assert!(Throttle::new("s3", &config, Arc::new(Metrics::new())).is_err());
+    }
+
+    #[test]
+    fn burst_passes_then_debt_is_paced() {
+        let throttle = throttle(1000, 500);
+        let now = Instant::now();
+
+        assert_eq!(throttle.reserve(500.0, now), Duration::ZERO);
+        assert_eq!(throttle.reserve(250.0, now), Duration::from_millis(250));
+        // Queued behind the earlier debt.
+        assert_eq!(throttle.reserve(250.0, now), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn refills_up_to_burst() {
+        let throttle = throttle(1000, 500);
+        let now = Instant::now();
+
+        throttle.reserve(500.0, now);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(throttle.reserve(500.0, later), Duration::ZERO);
+        assert_eq!(throttle.reserve(100.0, later), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn records_bytes_and_wait() {
+        let throttle = throttle(10_000, 100);
+
+        throttle.consume(100, "read").await;
+        throttle.consume(200, "read").await;
+
+        let rendered = throttle.metrics.render_prometheus();
+        assert!(
+            rendered.contains(r#"cachegate_throttle_bytes_total{direction="read",store="s3"} 300"#)
+        );
+        let waited: u64 = rendered
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix(
+                    r#"cachegate_throttle_wait_ms_total{direction="read",store="s3"} "#,
+                )
+            })
+            .and_then(|value| value.parse().ok())
+            .unwrap();
+        assert!((15..=20).contains(&waited));
+    }
+
+    #[tokio::test]
+    async fn cancelled_waits_leave_the_gauge() {
+        let throttle = throttle(1000, 0);
+
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(10), throttle.consume(1000, "read")).await;
+        assert!(cancelled.is_err());
+
+        let rendered = throttle.metrics.render_prometheus();
+        assert!(rendered.contains(r#"cachegate_throttle_waiting{store="s3"} 0"#));
+    }
+}
@@ -390,6 +390,679 @@ stores:
     assert!(metrics.contains("cachegate_upstream_latency_ms_bucket"));
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_filesystem_readthrough() {
+    let root = tempdir().expect("temp store root");
+    let object_key = "nested/fixture.txt";
+    let payload = b"cachegate filesystem test".to_vec();
+    std::fs::create_dir_all(root.path().join("nested")).expect("create nested dir");
+    std::fs::write(root.path().join(object_key), &payload).expect("write fixture");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        &bearer_auth(),
+        &format!(
+            r#"local:
+  type: filesystem
+  root: "{root_path}"
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let sig = build_sig(&server.signing_key, "local", object_key, "GET");
+    let response = http
+        .get(format!("{base_url}/local/{object_key}?sig={sig}"))
+        .send()
+        .await
+        .expect("get fixture");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.bytes().await.expect("read fixture body");
+    assert_eq!(body.as_ref(), payload.as_slice());
+
+    let put_key = "uploads/new.txt";
+    let put_payload = b"cachegate filesystem put".to_vec();
+    let put_url = format!("{base_url}/local/{put_key}");
+    let put_response = http
+        .put(&put_url)
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .body(put_payload.clone())
+        .send()
+        .await
+        .expect("put upload");
+    assert_eq!(put_response.status(), StatusCode::OK);
+    let on_disk = std::fs::read(root.path().join(put_key)).expect("read uploaded file");
+    assert_eq!(on_disk, put_payload);
+
+    let head_response = http
+        .head(&put_url)
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("head upload");
+    assert_eq!(head_response.status(), StatusCode::OK);
+
+    let missing = http
+        .get(format!("{base_url}/local/missing.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("get missing");
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_prefix_mount_and_rewrite() {
+    let root = tempdir().expect("temp store root");
+    std::fs::create_dir_all(root.path().join("tenant-a/public/images/42")).expect("create dirs");
+    std::fs::write(root.path().join("tenant-a/public/hello.txt"), b"hello").expect("write hello");
+    std::fs::write(
+        root.path().join("tenant-a/public/images/42/original.jpg"),
+        b"jpeg",
+    )
+    .expect("write image");
+    std::fs::write(root.path().join("tenant-a/secret.txt"), b"secret").expect("write secret");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        &bearer_auth(),
+        &format!(
+            r#"tenant:
+  type: filesystem
+  root: "{root_path}"
+  prefix: "tenant-a/public/"
+  rewrite:
+    - pattern: '^img/(?P<id>[0-9]+)\.jpg$'
+      template: "images/$id/original.jpg"
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let get = |path: &str| {
+        http.get(format!("{base_url}/tenant/{path}"))
+            .bearer_auth(TEST_BEARER_TOKEN)
+            .send()
+    };
+
+    let response = get("hello.txt").await.expect("get mounted object");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+
+    let response = get("img/42.jpg").await.expect("get rewritten object");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"jpeg");
+
+    let response = get("%2E%2E/secret.txt").await.expect("get traversal");
+    assert_ne!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_store_method_policy() {
+    let root = tempdir().expect("temp store root");
+    std::fs::create_dir_all(root.path().join("assets")).expect("create assets dir");
+    std::fs::create_dir_all(root.path().join("ingest")).expect("create ingest dir");
+    std::fs::write(root.path().join("assets/logo.txt"), b"logo").expect("write asset");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        &bearer_auth(),
+        &format!(
+            r#"assets:
+  type: filesystem
+  root: "{root_path}/assets"
+  read_only: true
+  disable_prefetch: true
+ingest:
+  type: filesystem
+  root: "{root_path}/ingest"
+  write_only: true
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let response = http
+        .get(format!("{base_url}/assets/logo.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("get read-only asset");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = http
+        .put(format!("{base_url}/assets/new.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .body("nope")
+        .send()
+        .await
+        .expect("put read-only asset");
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()["allow"], "GET, HEAD");
+    assert!(!root.path().join("assets/new.txt").exists());
+
+    let response = http
+        .head(format!("{base_url}/assets/logo.txt?prefetch=true"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("head with prefetch");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = http
+        .put(format!("{base_url}/ingest/upload.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .body("payload")
+        .send()
+        .await
+        .expect("put write-only upload");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = http
+        .get(format!("{base_url}/ingest/upload.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("get write-only upload");
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()["allow"], "PUT");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_presign_upload_limits() {
+    let root = tempdir().expect("temp store root");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        "",
+        &format!(
+            r#"ingest:
+  type: filesystem
+  root: "{root_path}"
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+    let sig = sign_payload(
+        &server.signing_key,
+        serde_json::json!({
+            "v": 1,
+            "exp": unix_timestamp() + 300,
+            "m": "PUT",
+            "b": "ingest",
+            "p": "avatar.png",
+            "min_len": 4,
+            "max_len": 16,
+            "ct": ["image/*"],
+            "ip": ["127.0.0.0/8"],
+        }),
+    );
+    let put = |content_type: &'static str, body: &'static str| {
+        http.put(format!("{base_url}/ingest/avatar.png?sig={sig}"))
+            .header("content-type", content_type)
+            .body(body)
+            .send()
+    };
+
+    let response = put("text/html", "<html>").await.expect("put wrong type");
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = put("image/png", "far too long for the signed limit")
+        .await
+        .expect("put oversized");
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = put("image/png", "ab").await.expect("put undersized");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(!root.path().join("avatar.png").exists());
+
+    let response = put("image/png", "png bytes")
+        .await
+        .expect("put within limits");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        std::fs::read(root.path().join("avatar.png")).expect("read upload"),
+        b"png bytes"
+    );
+
+    let sig = sign_payload(
+        &server.signing_key,
+        serde_json::json!({
+            "v": 1,
+            "exp": unix_timestamp() + 300,
+            "nbf": unix_timestamp() + 300,
+            "m": "PUT",
+            "b": "ingest",
+            "p": "avatar.png",
+        }),
+    );
+    let response = http
+        .put(format!("{base_url}/ingest/avatar.png?sig={sig}"))
+        .body("png bytes")
+        .send()
+        .await
+        .expect("put before nbf");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let sig = sign_payload(
+        &server.signing_key,
+        serde_json::json!({
+            "v": 1,
+            "exp": unix_timestamp() + 300,
+            "m": "PUT",
+            "b": "ingest",
+            "p": "invoice.pdf",
+            "nonce": "7f3a",
+            "max_uses": 1,
+            "max_len": 9,
+        }),
+    );
+    // The rejected upload gives its use back.
+    for (body, expected) in [
+        ("oversized pdf bytes", StatusCode::PAYLOAD_TOO_LARGE),
+        ("pdf bytes", StatusCode::OK),
+        ("pdf bytes", StatusCode::GONE),
+    ] {
+        let response = http
+            .put(format!("{base_url}/ingest/invoice.pdf?sig={sig}"))
+            .body(body)
+            .send()
+            .await
+            .expect("put single-use");
+        assert_eq!(response.status(), expected);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_sign_endpoint_and_cli() {
+    let root = tempdir().expect("temp store root");
+    std::fs::write(root.path().join("a b.txt"), b"first").expect("seed a");
+    std::fs::create_dir(root.path().join("docs")).expect("seed dir");
+    std::fs::write(root.path().join("docs/b.txt"), b"second").expect("seed b");
+    std::fs::create_dir_all(root.path().join("albums/123")).expect("seed album dir");
+    std::fs::write(root.path().join("albums/123/cover.jpg"), b"cover").expect("seed cover");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        &format!(
+            r#"bearer_tokens:
+  - name: signer
+    token: "{TEST_BEARER_TOKEN}"
+  - name: docs-reader
+    token: "docs-token"
+    prefixes: ["docs/"]
+    methods: ["GET"]
+  - name: albums-signer
+    token: "albums-token"
+    prefixes: ["albums/"]
+"#
+        ),
+        &format!(
+            r#"files:
+  type: filesystem
+  root: "{root_path}"
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let response = http
+        .post(format!("{base_url}/_sign"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .json(&serde_json::json!({
+            "method": "GET",
+            "bucket": "files",
+            "paths": ["a b.txt", "docs/b.txt"],
+            "ttl": "5m",
+            "base_url": base_url,
+        }))
+        .send()
+        .await
+        .expect("sign batch");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await.expect("sign body");
+    let urls = body["urls"].as_array().expect("urls");
+    assert_eq!(urls.len(), 2);
+    for (signed, expected) in urls.iter().zip(["first", "second"]) {
+        let url = signed["url"].as_str().expect("url");
+        let response = http.get(url).send().await.expect("get signed url");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.expect("body"), expected);
+    }
+    let response = http
+        .put(urls[0]["url"].as_str().expect("url"))
+        .body("overwrite")
+        .send()
+        .await
+        .expect("put with get url");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let sign_as = |token: &'static str, paths: serde_json::Value| {
+        http.post(format!("{base_url}/_sign"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "method": "GET",
+                "bucket": "files",
+                "paths": paths,
+                "ttl": "5m",
+            }))
+            .send()
+    };
+    let response = sign_as("docs-token", serde_json::json!(["docs/b.txt"]))
+        .await
+        .expect("sign in scope");
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = sign_as("docs-token", serde_json::json!(["docs/b.txt", "a b.txt"]))
+        .await
+        .expect("sign out of scope");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = sign_as("wrong-token", serde_json::json!(["docs/b.txt"]))
+        .await
+        .expect("sign unauthenticated");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = http
+        .post(format!("{base_url}/_sign"))
+        .bearer_auth("albums-token")
+        .json(&serde_json::json!({
+            "method": "GET",
+            "bucket": "files",
+            "paths": ["albums/123/"],
+            "ttl": "5m",
+            "prefix": true,
+        }))
+        .send()
+        .await
+        .expect("sign scoped prefix");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await.expect("sign body");
+    let signed = &body["urls"][0];
+    assert_eq!(signed["path"], "albums/123");
+    let sig = signed["sig"].as_str().expect("sig");
+    let response = http
+        .get(format!("{base_url}/files/albums/123/cover.jpg?sig={sig}"))
+        .send()
+        .await
+        .expect("get under signed prefix");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.expect("body"), "cover");
+
+    let response = http
+        .post(format!("{base_url}/_sign"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .json(&serde_json::json!({
+            "method": "GET",
+            "bucket": "files",
+            "paths": ["docs/b.txt"],
+            "ttl": "500000000000y",
+        }))
+        .send()
+        .await
+        .expect("sign oversized ttl");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cachegate"))
+        .arg("--config")
+        .arg(server.config_file.path())
+        .args(["presign", "--bucket", "files", "--path", "docs/b.txt"])
+        .args(["--ttl", "500000000000y"])
+        .output()
+        .expect("run presign with oversized ttl");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ttl must be at most"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cachegate"))
+        .arg("--config")
+        .arg(server.config_file.path())
+        .args(["presign", "--bucket", "files", "--path", "docs/b.txt"])
+        .args(["--ttl", "1m", "--base-url", base_url])
+        .output()
+        .expect("run presign");
+    assert!(output.status.success());
+    let url = String::from_utf8(output.stdout).expect("presign output");
+    let response = http.get(url.trim()).send().await.expect("get cli url");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.expect("body"), "second");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_config_reload() {
+    let root = tempdir().expect("temp store root");
+    std::fs::create_dir_all(root.path().join("a")).expect("create a dir");
+    std::fs::create_dir_all(root.path().join("b")).expect("create b dir");
+    std::fs::write(root.path().join("b/hello.txt"), b"hello").expect("write object");
+
+    let root_path = root.path().display();
+    let store_a = format!("a:\n  type: filesystem\n  root: \"{root_path}/a\"\n");
+    let server = TestServer::start(&bearer_auth(), &store_a).await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let response = http
+        .get(format!("{base_url}/b/hello.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("get before reload");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let rotated = "rotated-token";
+    let rotated_auth = format!("bearer_token: \"{rotated}\"");
+    let store_b = format!("{store_a}b:\n  type: filesystem\n  root: \"{root_path}/b\"\n");
+    server.rewrite_config(&rotated_auth, &store_b);
+    let response = http
+        .post(format!("{base_url}/_reload"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("reload");
+    assert_eq!(response.status(), StatusCode::OK);
+    let diff: serde_json::Value = response.json().await.expect("reload diff");
+    assert_eq!(diff["added"], serde_json::json!(["b"]));
+    assert_eq!(diff["auth_changed"], serde_json::json!(true));
+
+    let response = http
+        .get(format!("{base_url}/b/hello.txt"))
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .expect("get after reload");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.expect("body"), "hello");
+
+    let response = http
+        .get(format!("{base_url}/b/hello.txt"))
+        .bearer_auth(TEST_BEARER_TOKEN)
+        .send()
+        .await
+        .expect("get with old token");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let invalid = format!("{store_b}  rewrite:\n    - pattern: \"(\"\n      template: \"x\"\n");
+    server.rewrite_config(&rotated_auth, &invalid);
+    let response = http
+        .post(format!("{base_url}/_reload"))
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .expect("invalid reload");
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = http
+        .get(format!("{base_url}/b/hello.txt"))
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .expect("get after rejected reload");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Pointing a cached store at a new root must not keep serving the old bytes.
+    std::fs::create_dir_all(root.path().join("c")).expect("create c dir");
+    std::fs::write(root.path().join("c/hello.txt"), b"moved").expect("write moved object");
+    let moved = format!("{store_a}b:\n  type: filesystem\n  root: \"{root_path}/c\"\n");
+    server.rewrite_config(&rotated_auth, &moved);
+    let response = http
+        .post(format!("{base_url}/_reload"))
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .expect("reload changed store");
+    assert_eq!(response.status(), StatusCode::OK);
+    let diff: serde_json::Value = response.json().await.expect("reload diff");
+    assert_eq!(diff["changed"], serde_json::json!(["b"]));
+
+    let response = http
+        .get(format!("{base_url}/b/hello.txt"))
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .expect("get after changed store");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.expect("body"), "moved");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn live_ready_endpoint() {
+    let root = tempdir().expect("temp store root");
+    std::fs::create_dir_all(root.path().join("assets")).expect("create assets dir");
+    std::fs::create_dir_all(root.path().join("scratch")).expect("create scratch dir");
+    std::fs::write(root.path().join("assets/canary.txt"), b"ok").expect("write canary");
+
+    let root_path = root.path().display();
+    let server = TestServer::start(
+        "",
+        &format!(
+            r#"assets:
+  type: filesystem
+  root: "{root_path}/assets"
+  readiness:
+    canary: "canary.txt"
+    interval: 0s
+scratch:
+  type: filesystem
+  root: "{root_path}/scratch"
+  readiness:
+    canary: "missing.txt"
+    critical: false
+"#
+        ),
+    )
+    .await;
+    let (base_url, http) = (&server.base_url, &server.http);
+
+    let response = http
+        .get(format!("{base_url}/ready"))
+        .send()
+        .await
+        .expect("ready");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await.expect("ready body");
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["stores"]["assets"]["status"], "ok");
+    assert_eq!(body["stores"]["scratch"]["status"], "down");
+    assert_eq!(body["cache"]["local"]["status"], "ok");
+
+    std::fs::remove_file(root.path().join("assets/canary.txt")).expect("remove canary");
+    let response = http
+        .get(format!("{base_url}/ready"))
+        .send()
+        .await
+        .expect("ready after canary removal");
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = response.json().await.expect("ready body");
+    assert_eq!(body["status"], "not_ready");
+}
+
+/// A cachegate process on a free port, signing with a fixed key and a small memory cache.
+struct TestServer {
+    base_url: String,
+    http: reqwest::Client,
+    signing_key: SigningKey,
+    listen: String,
+    config_file: tempfile::NamedTempFile,
+    _guard: ChildGuard,
+}
+
+impl TestServer {
+    /// `auth` holds extra `auth` keys and `stores` the store entries, both unindented YAML.
+    async fn start(auth: &str, stores: &str) -> Self {
+        let listen = format!("127.0.0.1:{}", free_port());
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut config_file = tempfile::NamedTempFile::new().expect("temp config");
+        config_file
+            .write_all(test_config(&listen, &signing_key, auth, stores).as_bytes())
+            .expect("write config");
+
+        let child = Command::new(env!("CARGO_BIN_EXE_cachegate"))
+            .arg("--config")
+            .arg(config_file.path())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("start cachegate");
+        let guard = ChildGuard::new(child);
+
+        let base_url = format!("http://{listen}");
+        wait_for_ready(&base_url).await;
+        Self {
+            base_url,
+            http: reqwest::Client::new(),
+            signing_key,
+            listen,
+            config_file,
+            _guard: guard,
+        }
+    }
+
+    /// Replaces the config on disk; the running process picks it up on reload.
+    fn rewrite_config(&self, auth: &str, stores: &str) {
+        let config = test_config(&self.listen, &self.signing_key, auth, stores);
+        std::fs::write(self.config_file.path(), config).expect("rewrite config");
+    }
+}
+
+fn test_config(listen: &str, signing_key: &SigningKey, auth: &str, stores: &str) -> String {
+    let public_b64 = URL_SAFE_NO_PAD.encode(signing_key.verifying_key().as_bytes());
+    let private_b64 = URL_SAFE_NO_PAD.encode(signing_key.to_bytes());
+    let indent = |yaml: &str| {
+        yaml.lines()
+            .map(|line| format!("  {line}\n"))
+            .collect::<String>()
+    };
+    let auth = indent(auth);
+    let stores = indent(stores);
+    format!(
+        r#"listen: "{listen}"
+
+auth:
+  public_key: "{public_b64}"
+  private_key: "{private_b64}"
+{auth}
+cache:
+  max_memory: 10MB
+  max_object_size: 1MiB
+
+stores:
+{stores}"#
+    )
+}
+
+fn bearer_auth() -> String {
+    format!("bearer_token: \"{TEST_BEARER_TOKEN}\"")
+}
+
+fn sign_payload(signing_key: &SigningKey, payload: serde_json::Value) -> String {
+    let payload_bytes = serde_json::to_vec(&payload).expect("payload json");
+    let signature: Signature = signing_key.sign(&payload_bytes);
+    let payload_b64 = URL_SAFE_NO_PAD.encode(payload_bytes);
+    let signature_b64 = URL_SAFE_NO_PAD.encode(signature.to_bytes());
+    format!("{payload_b64}.{signature_b64}")
+}
+
 fn build_sig(signing_key: &SigningKey, bucket: &str, path: &str, method: &str) -> String {
     let payload = PresignPayload {
         v: 1,